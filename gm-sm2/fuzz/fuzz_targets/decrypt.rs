@@ -0,0 +1,21 @@
+//! The first byte selects the layout: bit 0 for a compressed C1, bit 1 for
+//! C1C3C2. The rest is fed to `Sm2PrivateKey::decrypt`, which must return an
+//! error instead of panicking on any malformed input.
+
+#![no_main]
+
+use gm_sm2::key::{Sm2Model, Sm2PrivateKey};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&mode, ciphertext)) = data.split_first() else {
+        return;
+    };
+    let sk = Sm2PrivateKey::new(&[0x11; 32]).unwrap();
+    let compressed = mode & 0x01 != 0;
+    let model = match mode & 0x02 {
+        0 => Sm2Model::C1C2C3,
+        _ => Sm2Model::C1C3C2,
+    };
+    let _ = sk.decrypt(ciphertext, compressed, model);
+});
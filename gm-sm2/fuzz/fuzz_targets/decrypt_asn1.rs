@@ -0,0 +1,12 @@
+//! Feed arbitrary bytes to `Sm2PrivateKey::decrypt_der`, which must return an
+//! error instead of panicking on any malformed input.
+
+#![no_main]
+
+use gm_sm2::key::Sm2PrivateKey;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let sk = Sm2PrivateKey::new(&[0x11; 32]).unwrap();
+    let _ = sk.decrypt_der(data);
+});
@@ -0,0 +1,245 @@
+//! Batch verification of SM2 signatures.
+//!
+//! SM2 signatures only carry `r = e + x1 mod n`, not the point `R`, so the
+//! equations cannot be folded into one random linear combination the way
+//! Schnorr signatures can. The batch instead shares the expensive parts:
+//!
+//! * `ZA` and the window table of every distinct `(public key, id)` are computed once,
+//!   and so is the window table of `G`;
+//! * `[s]G + [t]P` is one Straus pass ([`multi_scalar_mul_tables`]) over the
+//!   tables of `G` and `P`, so both terms share the doublings;
+//! * all the resulting points are converted to affine with a single field
+//!   inversion ([`batch_to_affine`]);
+//! * with the `rayon` feature the per-signature work runs in parallel.
+
+use std::collections::HashMap;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use gm_sm3::Sm3;
+
+use crate::error::{Sm2Error, Sm2Result};
+use crate::fields::fn64::{fn_add, SM2_N};
+use crate::fields::fp64::fp_from_mont;
+use crate::fields::FieldModOperation;
+use crate::key::Sm2PublicKey;
+use crate::p256_ecc::{batch_to_affine, g_mul, multi_scalar_mul_tables, Point};
+use crate::u256::{u256_cmp, u256_from_be_bytes, U256};
+use crate::util::{compute_za, DEFAULT_ID};
+
+/// One entry of a batch: `(public key, id, message, r || s signature)`.
+/// A `None` id means the default ID "1234567812345678".
+pub type BatchItem<'a> = (&'a Sm2PublicKey, Option<&'a [u8]>, &'a [u8], &'a [u8]);
+
+struct KeyContext {
+    za: [u8; 32],
+    table: [Point; 16],
+}
+
+struct Prepared {
+    r: U256,
+    e: U256,
+    point: Point,
+}
+
+/// Verify many signatures at once.
+///
+/// Returns `Ok(())` only if every signature is valid, otherwise the index and
+/// the error of each bad entry, in order.
+///
+/// # Example
+/// ```rust
+/// use gm_sm2::batch_verify;
+/// use gm_sm2::key::gen_keypair;
+/// fn main(){
+///     let (pk, sk) = gen_keypair().unwrap();
+///     let sig1 = sk.sign(None, b"first").unwrap();
+///     let sig2 = sk.sign(Some(b"alice"), b"second").unwrap();
+///     batch_verify(&[
+///         (&pk, None, b"first", &sig1),
+///         (&pk, Some(b"alice"), b"second", &sig2),
+///     ])
+///     .unwrap();
+///
+///     let errors = batch_verify(&[(&pk, None, b"first", &sig1), (&pk, None, b"first", &sig2)]);
+///     assert_eq!(1, errors.unwrap_err()[0].0);
+/// }
+/// ```
+pub fn batch_verify(items: &[BatchItem]) -> Result<(), Vec<(usize, Sm2Error)>> {
+    // distinct (key, id) pairs; the raw jacobian coordinates are used as the
+    // key so no inversion is needed to find duplicates
+    let mut slots: HashMap<(U256, U256, U256, &[u8]), usize> = HashMap::new();
+    let mut keys: Vec<(&Sm2PublicKey, &[u8])> = vec![];
+    let item_slots: Vec<usize> = items
+        .iter()
+        .map(|(pk, id, _, _)| {
+            let id = id.unwrap_or(DEFAULT_ID);
+            let p = &pk.point;
+            *slots.entry((p.x, p.y, p.z, id)).or_insert_with(|| {
+                keys.push((pk, id));
+                keys.len() - 1
+            })
+        })
+        .collect();
+
+    let contexts = map_all(&keys, |(pk, id)| -> Sm2Result<KeyContext> {
+        Ok(KeyContext {
+            za: compute_za(id, &pk.point)?,
+            table: pk.point.window_table(),
+        })
+    });
+
+    let g_table = g_mul(&[1, 0, 0, 0]).window_table();
+    let indexes: Vec<usize> = (0..items.len()).collect();
+    let prepared = map_all(&indexes, |&i| {
+        let (_, _, msg, sig) = items[i];
+        let ctx = contexts[item_slots[i]].as_ref().map_err(|e| *e)?;
+        prepare(&g_table, ctx, msg, sig)
+    });
+
+    let points: Vec<Point> = prepared
+        .iter()
+        .filter_map(|p| p.as_ref().ok().map(|p| p.point))
+        .collect();
+    let mut affine = batch_to_affine(&points).into_iter();
+
+    let mut errors = vec![];
+    for (i, p) in prepared.into_iter().enumerate() {
+        let p = match p {
+            Ok(p) => p,
+            Err(e) => {
+                errors.push((i, e));
+                continue;
+            }
+        };
+        let point = affine.next().unwrap();
+        let x1 = u256_from_be_bytes(&fp_from_mont(&point.x).to_byte_be());
+        if point.is_zero() || u256_cmp(&p.r, &fn_add(&x1, &p.e)) != 0 {
+            errors.push((i, Sm2Error::InvalidDigest));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+// range checks and (x1, y1) = [s]G + [r + s]P
+fn prepare(g_table: &[Point; 16], ctx: &KeyContext, msg: &[u8], sig: &[u8]) -> Sm2Result<Prepared> {
+    if sig.len() != 64 {
+        return Err(Sm2Error::InvalidSignature);
+    }
+    let r = u256_from_be_bytes(&sig[..32]);
+    let s = u256_from_be_bytes(&sig[32..]);
+    if r.is_zero() || s.is_zero() {
+        return Err(Sm2Error::ZeroSig);
+    }
+    if u256_cmp(&r, &SM2_N) >= 0 || u256_cmp(&s, &SM2_N) >= 0 {
+        return Err(Sm2Error::InvalidSignature);
+    }
+    let t = fn_add(&s, &r);
+    if t.is_zero() {
+        return Err(Sm2Error::InvalidSignature);
+    }
+
+    let mut hasher = Sm3::new();
+    hasher.update(&ctx.za);
+    hasher.update(msg);
+    let e = u256_from_be_bytes(&hasher.finalize());
+
+    let point = multi_scalar_mul_tables(&[*g_table, ctx.table], &[s, t]);
+    Ok(Prepared { r, e, point })
+}
+
+#[cfg(feature = "rayon")]
+fn map_all<T: Sync, U: Send>(items: &[T], f: impl Fn(&T) -> U + Sync + Send) -> Vec<U> {
+    items.par_iter().map(f).collect()
+}
+
+#[cfg(not(feature = "rayon"))]
+fn map_all<T, U>(items: &[T], f: impl Fn(&T) -> U) -> Vec<U> {
+    items.iter().map(f).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::batch::batch_verify;
+    use crate::error::Sm2Error;
+    use crate::key::gen_keypair;
+    use crate::p256_ecc::{batch_to_affine, g_mul, multi_scalar_mul, Point};
+
+    #[test]
+    fn test_batch_verify() {
+        let keys: Vec<_> = (0..3).map(|_| gen_keypair().unwrap()).collect();
+        let ids: [Option<&[u8]>; 2] = [None, Some(b"alice@example.com")];
+        let msgs: Vec<Vec<u8>> = (0..20u8).map(|i| vec![i; i as usize * 7]).collect();
+        let mut sigs = vec![];
+        for (i, msg) in msgs.iter().enumerate() {
+            let (_, sk) = &keys[i % 3];
+            sigs.push(sk.sign(ids[i % 2], msg).unwrap());
+        }
+        let items: Vec<_> = msgs
+            .iter()
+            .enumerate()
+            .map(|(i, msg)| (&keys[i % 3].0, ids[i % 2], &msg[..], &sigs[i][..]))
+            .collect();
+        batch_verify(&items).unwrap();
+        batch_verify(&[]).unwrap();
+
+        let mut bad = items.clone();
+        bad[3].2 = b"tampered";
+        bad[7].1 = Some(b"mallory");
+        bad[11].0 = &keys[0].0;
+        bad[15].3 = &sigs[15][..63];
+        bad[18].3 = &[0u8; 64];
+        bad[19].3 = &[0xff; 64];
+        let errors = batch_verify(&bad).unwrap_err();
+        assert_eq!(
+            vec![
+                (3, Sm2Error::InvalidDigest),
+                (7, Sm2Error::InvalidDigest),
+                (11, Sm2Error::InvalidDigest),
+                (15, Sm2Error::InvalidSignature),
+                (18, Sm2Error::ZeroSig),
+                (19, Sm2Error::InvalidSignature),
+            ],
+            errors
+        );
+        for (i, (pk, id, msg, sig)) in bad.iter().enumerate() {
+            let single = pk.verify(*id, msg, sig);
+            assert_eq!(single.is_err(), errors.iter().any(|(j, _)| *j == i));
+        }
+    }
+
+    #[test]
+    fn test_batch_verify_id_too_long() {
+        let (pk, sk) = gen_keypair().unwrap();
+        let sig = sk.sign(None, b"hello").unwrap();
+        let id = vec![0u8; 8192];
+        let errors = batch_verify(&[
+            (&pk, Some(&id), b"hello", &sig),
+            (&pk, None, b"hello", &sig),
+            (&pk, Some(&id), b"world", &sig),
+        ])
+        .unwrap_err();
+        assert_eq!(vec![(0, Sm2Error::IdTooLong), (2, Sm2Error::IdTooLong)], errors);
+    }
+
+    #[test]
+    fn test_multi_scalar_mul_and_batch_affine() {
+        let a = [0x1234, 0x5678, 0x9abc, 0x0def];
+        let b = [0xffffffffffffffff, 0x1, 0x0, 0x7fffffffffffffff];
+        let p = g_mul(&[7, 0, 0, 0]);
+        let q = g_mul(&[11, 0, 0, 0]);
+        let expected = p.scalar_mul(&a).point_add(&q.scalar_mul(&b));
+        let r = multi_scalar_mul(&[p, q], &[a, b]);
+
+        let affine = batch_to_affine(&[expected, Point::zero(), r, p]);
+        assert_eq!(expected.to_affine_point(), affine[0]);
+        assert!(affine[1].is_zero());
+        assert_eq!(affine[0], affine[2]);
+        assert_eq!(p.to_affine_point(), affine[3]);
+    }
+}
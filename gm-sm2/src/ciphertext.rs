@@ -0,0 +1,313 @@
+//! Typed SM2 ciphertexts and the wire layouts they are exchanged in.
+//!
+//! The standard concatenates `C1 || C3 || C2` (`C1 || C2 || C3` in the 2010
+//! draft), GM/T 0009 wraps the same fields in a DER `SM2Cipher` sequence, and
+//! some Java libraries strip the `0x04` prefix of an uncompressed `C1`.
+//! [`Sm2Ciphertext`] holds the fields independently of the layout, converts
+//! between layouts and, through [`Sm2Ciphertext::candidates`], lists every
+//! layout a byte string can be read as.
+//!
+//! # Example
+//! ```rust
+//! use gm_sm2::ciphertext::{C1Format, Sm2Ciphertext, Sm2CiphertextFormat};
+//! use gm_sm2::key::{gen_keypair, Sm2Model};
+//! fn main(){
+//!     let (pk, sk) = gen_keypair().unwrap();
+//!     let encrypted = pk.encrypt(b"hello", false, Sm2Model::C1C3C2).unwrap();
+//!     let ciphertext = Sm2Ciphertext::from_bytes(
+//!         &encrypted,
+//!         Sm2CiphertextFormat::Raw(C1Format::Uncompressed, Sm2Model::C1C3C2),
+//!     )
+//!     .unwrap();
+//!
+//!     // what a partner might send instead
+//!     let der = ciphertext.to_bytes(Sm2CiphertextFormat::Asn1);
+//!     let java = ciphertext.to_bytes(Sm2CiphertextFormat::Raw(C1Format::Unprefixed, Sm2Model::C1C2C3));
+//!     assert_eq!(b"hello".to_vec(), sk.decrypt_any(&der).unwrap());
+//!     assert_eq!(b"hello".to_vec(), sk.decrypt_any(&java).unwrap());
+//! }
+//! ```
+
+use num_bigint::BigUint;
+
+use crate::error::{Sm2Error, Sm2Result};
+use crate::key::{read_c1, Sm2Model};
+use crate::p256_ecc::Point;
+
+/// Encoding of the point `C1` in a concatenated ciphertext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum C1Format {
+    /// `04 || x || y`, 65 bytes.
+    Uncompressed,
+    /// `02 || x` or `03 || x`, 33 bytes.
+    Compressed,
+    /// `x || y` without the `04` prefix, 64 bytes.
+    Unprefixed,
+}
+
+impl C1Format {
+    fn len(&self) -> usize {
+        match self {
+            C1Format::Uncompressed => 65,
+            C1Format::Compressed => 33,
+            C1Format::Unprefixed => 64,
+        }
+    }
+}
+
+/// Wire layout of an SM2 ciphertext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sm2CiphertextFormat {
+    /// The three fields concatenated in the order of the model.
+    Raw(C1Format, Sm2Model),
+    /// GM/T 0009 `SM2Cipher ::= SEQUENCE { x INTEGER, y INTEGER, hash OCTET STRING, cipherText OCTET STRING }`.
+    Asn1,
+}
+
+/// An SM2 ciphertext split into `C1`, `C3` and `C2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sm2Ciphertext {
+    c1: Point,
+    c3: [u8; 32],
+    c2: Vec<u8>,
+}
+
+impl Sm2Ciphertext {
+    /// Parse `bytes` in the given layout.
+    ///
+    /// `C1` must be a point on the curve and `C3` 32 bytes; `C2` is empty for
+    /// an empty message. The whole input must be consumed.
+    pub fn from_bytes(bytes: &[u8], format: Sm2CiphertextFormat) -> Sm2Result<Sm2Ciphertext> {
+        match format {
+            Sm2CiphertextFormat::Raw(c1_format, model) => Self::from_raw(bytes, c1_format, model),
+            Sm2CiphertextFormat::Asn1 => Self::from_asn1(bytes),
+        }
+    }
+
+    /// Every `(ciphertext, layout)` that `bytes` parses as, most common layout
+    /// first: DER, then the prefixed point encodings, then the unprefixed one,
+    /// each with `C1C3C2` before `C1C2C3`.
+    ///
+    /// The order of `C2` and `C3` cannot be told apart without the private key,
+    /// so a concatenated ciphertext always yields two candidates per point
+    /// encoding. [`Sm2PrivateKey::decrypt_any`] picks the one whose `C3` checks.
+    ///
+    /// [`Sm2PrivateKey::decrypt_any`]: crate::key::Sm2PrivateKey::decrypt_any
+    pub fn candidates(bytes: &[u8]) -> Vec<(Sm2Ciphertext, Sm2CiphertextFormat)> {
+        candidate_formats()
+            .into_iter()
+            .filter_map(|format| {
+                Self::from_bytes(bytes, format)
+                    .ok()
+                    .map(|ciphertext| (ciphertext, format))
+            })
+            .collect()
+    }
+
+    /// Encode the ciphertext in the given layout.
+    pub fn to_bytes(&self, format: Sm2CiphertextFormat) -> Vec<u8> {
+        match format {
+            Sm2CiphertextFormat::Raw(c1_format, model) => {
+                let mut out = self.c1_bytes(c1_format);
+                match model {
+                    Sm2Model::C1C2C3 => {
+                        out.extend_from_slice(&self.c2);
+                        out.extend_from_slice(&self.c3);
+                    }
+                    Sm2Model::C1C3C2 => {
+                        out.extend_from_slice(&self.c3);
+                        out.extend_from_slice(&self.c2);
+                    }
+                }
+                out
+            }
+            Sm2CiphertextFormat::Asn1 => {
+                let c1 = self.c1.to_byte_be(false);
+                let x = BigUint::from_bytes_be(&c1[1..33]);
+                let y = BigUint::from_bytes_be(&c1[33..]);
+                yasna::construct_der(|writer| {
+                    writer.write_sequence(|writer| {
+                        writer.next().write_biguint(&x);
+                        writer.next().write_biguint(&y);
+                        writer.next().write_bytes(&self.c3);
+                        writer.next().write_bytes(&self.c2);
+                    });
+                })
+            }
+        }
+    }
+
+    /// The point `C1 = [k]G`, in affine form.
+    pub fn c1(&self) -> &Point {
+        &self.c1
+    }
+
+    /// `C1` in the given encoding.
+    pub fn c1_bytes(&self, c1_format: C1Format) -> Vec<u8> {
+        match c1_format {
+            C1Format::Uncompressed => self.c1.to_byte_be(false),
+            C1Format::Compressed => self.c1.to_byte_be(true),
+            C1Format::Unprefixed => self.c1.to_byte_be(false)[1..].to_vec(),
+        }
+    }
+
+    /// The hash `C3 = SM3(x2 || M || y2)`.
+    pub fn c3(&self) -> &[u8; 32] {
+        &self.c3
+    }
+
+    /// The masked message `C2`.
+    pub fn c2(&self) -> &[u8] {
+        &self.c2
+    }
+
+    fn from_raw(bytes: &[u8], c1_format: C1Format, model: Sm2Model) -> Sm2Result<Sm2Ciphertext> {
+        let c1_len = c1_format.len();
+        if bytes.len() < c1_len + 32 {
+            return Err(Sm2Error::InvalidCiphertext);
+        }
+        let (c1_bytes, rest) = bytes.split_at(c1_len);
+        let c1 = match c1_format {
+            C1Format::Uncompressed => read_c1(c1_bytes, false)?,
+            C1Format::Compressed => read_c1(c1_bytes, true)?,
+            C1Format::Unprefixed => {
+                let mut prefixed = vec![0x04];
+                prefixed.extend_from_slice(c1_bytes);
+                read_c1(&prefixed, false)?
+            }
+        };
+        let (c2, c3) = match model {
+            Sm2Model::C1C2C3 => rest.split_at(rest.len() - 32),
+            Sm2Model::C1C3C2 => {
+                let (c3, c2) = rest.split_at(32);
+                (c2, c3)
+            }
+        };
+        Ok(Sm2Ciphertext {
+            c1,
+            c3: c3.try_into().unwrap(),
+            c2: c2.to_vec(),
+        })
+    }
+
+    fn from_asn1(bytes: &[u8]) -> Sm2Result<Sm2Ciphertext> {
+        let (x, y, c3, c2) = yasna::parse_der(bytes, |reader| {
+            reader.read_sequence(|reader| {
+                let x = reader.next().read_biguint()?;
+                let y = reader.next().read_biguint()?;
+                let c3 = reader.next().read_bytes()?;
+                let c2 = reader.next().read_bytes()?;
+                Ok((x, y, c3, c2))
+            })
+        })
+        .map_err(|_| Sm2Error::InvalidDer)?;
+        let c3: [u8; 32] = c3.try_into().map_err(|_| Sm2Error::InvalidDer)?;
+        let mut c1_bytes = vec![0x04];
+        c1_bytes.extend_from_slice(&coordinate_to_bytes(&x)?);
+        c1_bytes.extend_from_slice(&coordinate_to_bytes(&y)?);
+        Ok(Sm2Ciphertext {
+            c1: read_c1(&c1_bytes, false)?,
+            c3,
+            c2,
+        })
+    }
+}
+
+/// Every layout [`Sm2Ciphertext::candidates`] tries, in order.
+fn candidate_formats() -> Vec<Sm2CiphertextFormat> {
+    let mut formats = vec![Sm2CiphertextFormat::Asn1];
+    for c1_format in [
+        C1Format::Uncompressed,
+        C1Format::Compressed,
+        C1Format::Unprefixed,
+    ] {
+        for model in [Sm2Model::C1C3C2, Sm2Model::C1C2C3] {
+            formats.push(Sm2CiphertextFormat::Raw(c1_format, model));
+        }
+    }
+    formats
+}
+
+/// Left-pad an ASN.1 INTEGER coordinate to 32 bytes.
+fn coordinate_to_bytes(a: &BigUint) -> Sm2Result<[u8; 32]> {
+    let bytes = a.to_bytes_be();
+    if bytes.len() > 32 {
+        return Err(Sm2Error::InvalidDer);
+    }
+    let mut out = [0u8; 32];
+    out[32 - bytes.len()..].copy_from_slice(&bytes);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ciphertext::{candidate_formats, C1Format, Sm2Ciphertext, Sm2CiphertextFormat};
+    use crate::error::Sm2Error;
+    use crate::key::{gen_keypair, Sm2Model};
+
+    #[test]
+    fn test_convert_layouts() {
+        let (pk, sk) = gen_keypair().unwrap();
+        let msg = b"partner ciphertext";
+        for compressed in [false, true] {
+            for model in [Sm2Model::C1C2C3, Sm2Model::C1C3C2] {
+                let encrypted = pk.encrypt(msg, compressed, model).unwrap();
+                let c1_format = match compressed {
+                    true => C1Format::Compressed,
+                    false => C1Format::Uncompressed,
+                };
+                let format = Sm2CiphertextFormat::Raw(c1_format, model);
+                let ciphertext = Sm2Ciphertext::from_bytes(&encrypted, format).unwrap();
+                assert_eq!(encrypted, ciphertext.to_bytes(format));
+                assert_eq!(msg.len(), ciphertext.c2().len());
+
+                for format in candidate_formats() {
+                    let bytes = ciphertext.to_bytes(format);
+                    let parsed = Sm2Ciphertext::from_bytes(&bytes, format).unwrap();
+                    assert_eq!(ciphertext, parsed);
+                    assert!(Sm2Ciphertext::candidates(&bytes).contains(&(parsed, format)));
+                    assert_eq!(msg.to_vec(), sk.decrypt_ciphertext(&ciphertext).unwrap());
+                    assert_eq!(msg.to_vec(), sk.decrypt_any(&bytes).unwrap());
+                }
+            }
+        }
+
+        // an empty message has an empty C2 in every layout
+        let encrypted = pk.encrypt(b"", false, Sm2Model::C1C3C2).unwrap();
+        let format = Sm2CiphertextFormat::Raw(C1Format::Uncompressed, Sm2Model::C1C3C2);
+        let ciphertext = Sm2Ciphertext::from_bytes(&encrypted, format).unwrap();
+        for format in candidate_formats() {
+            let bytes = ciphertext.to_bytes(format);
+            assert!(sk.decrypt_any(&bytes).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_decrypt_any_invalid() {
+        let (pk, sk) = gen_keypair().unwrap();
+        let (_, other) = gen_keypair().unwrap();
+        let encrypted = pk.encrypt(b"hello", false, Sm2Model::C1C3C2).unwrap();
+        assert!(other.decrypt_any(&encrypted).is_err());
+
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(sk.decrypt_any(&tampered).is_err());
+
+        for len in 0..encrypted.len() {
+            assert!(sk.decrypt_any(&encrypted[..len]).is_err());
+        }
+        assert_eq!(
+            Sm2Error::InvalidCiphertext,
+            sk.decrypt_any(&[0x30; 100]).unwrap_err()
+        );
+        assert!(Sm2Ciphertext::candidates(&[0x04; 200]).is_empty());
+        assert_eq!(
+            Sm2Error::InvalidCiphertext,
+            Sm2Ciphertext::from_bytes(
+                &encrypted,
+                Sm2CiphertextFormat::Raw(C1Format::Compressed, Sm2Model::C1C3C2)
+            )
+            .unwrap_err()
+        );
+    }
+}
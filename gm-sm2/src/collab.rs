@@ -0,0 +1,635 @@
+//! Two-party collaborative SM2 signing (协同签名).
+//!
+//! The private key `d` is split between a device and a server so that neither
+//! side alone can sign, and it is never assembled. Each party holds a
+//! multiplicative share with `(1 + d)^-1 = d1 * d2 mod n`:
+//!
+//! * key generation: each party publishes `[di^-1]G` and derives the public key
+//!   `P = [di^-1]Pj - G` from the share of the other party;
+//! * round 1, device: picks `k1` and sends `Q1 = [k1]G` with the digest `e`;
+//! * round 2, server: picks `k2, k3`, computes `(x1, y1) = [k3]Q1 + [k2]G`,
+//!   `r = e + x1`, `s2 = d2 * k3` and `s3 = d2 * (r + k2)`;
+//! * round 3, device: `s = d1 * k1 * s2 + d1 * s3 - r`.
+//!
+//! The joint nonce is `k = k1 * k3 + k2`, and `s = (1 + d)^-1 * (k + r) - r`
+//! equals the ordinary SM2 `(1 + d)^-1 * (k - r * d)`, so the result verifies
+//! with [`Sm2PublicKey::verify`].
+//!
+//! The same shares decrypt jointly (协同解密). Since `d = (d1 * d2)^-1 - 1`:
+//!
+//! * the device sends `C1` of the ciphertext;
+//! * the server answers with its partial point `T1 = [d2^-1]C1`;
+//! * the device computes `[d1^-1]T1 - C1 = [d]C1 = (x2, y2)` and finishes the
+//!   KDF and the `C3` check exactly like
+//!   [`Sm2PrivateKey::decrypt`](crate::key::Sm2PrivateKey::decrypt).
+//!
+//! Every message has a fixed-size byte encoding (`to_bytes` / `from_bytes`) so
+//! it can be sent over any transport.
+
+use std::fmt;
+
+use rand::{CryptoRng, RngCore};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::error::{Sm2Error, Sm2Result};
+use crate::fields::fn64::{fn_add, fn_mul, fn_pow, fn_sub, SM2_N, SM2_N_MINUS_TWO};
+use crate::fields::fp64::{fp_from_mont, random_u256_with_rng, SM2_P};
+use crate::fields::FieldModOperation;
+use crate::key::{decrypt_with_point, split_ciphertext, Sm2Model, Sm2PublicKey};
+use crate::p256_ecc::{g_mul, g_mul_ct, Point};
+use crate::u256::{u256_add, u256_cmp, u256_from_be_bytes, SM2_ONE, U256};
+
+/// Length of an encoded [`PublicShare`].
+pub const PUBLIC_SHARE_LEN: usize = 65;
+/// Length of an encoded [`SignRequest`].
+pub const SIGN_REQUEST_LEN: usize = 65 + 32;
+/// Length of an encoded [`SignResponse`].
+pub const SIGN_RESPONSE_LEN: usize = 32 * 3;
+/// Length of an encoded [`DecryptRequest`] or [`DecryptResponse`].
+pub const DECRYPT_MESSAGE_LEN: usize = 65;
+
+/// A freshly generated secret share `di` whose public key is not known yet.
+pub struct PartialKey {
+    d: U256,
+}
+
+/// `[di^-1]G`, published by each party during key generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicShare {
+    point: Point,
+}
+
+/// One party's share of the signing key, together with the joint public key.
+#[derive(Clone)]
+pub struct KeyShare {
+    d: U256,
+    public_key: Sm2PublicKey,
+}
+
+/// Device to server: `Q1 = [k1]G` and the digest `e = SM3(ZA || M)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignRequest {
+    q1: Point,
+    e: [u8; 32],
+}
+
+/// Server to device: `r`, `s2` and `s3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignResponse {
+    r: U256,
+    s2: U256,
+    s3: U256,
+}
+
+/// Device to server: the `C1` point of the ciphertext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecryptRequest {
+    c1: Point,
+}
+
+/// Server to device: the partial point `T1 = [d2^-1]C1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecryptResponse {
+    t1: Point,
+}
+
+/// Device side state between [`KeyShare::sign_request`] and [`SignSession::finish`].
+pub struct SignSession {
+    d1: U256,
+    k1: U256,
+    e: [u8; 32],
+    public_key: Sm2PublicKey,
+}
+
+impl PartialKey {
+    pub fn generate() -> PartialKey {
+        Self::generate_with_rng(&mut rand::thread_rng())
+    }
+
+    pub fn generate_with_rng<R: CryptoRng + RngCore>(rng: &mut R) -> PartialKey {
+        PartialKey {
+            d: random_u256_with_rng(rng),
+        }
+    }
+
+    pub fn public_share(&self) -> PublicShare {
+        let d_inv = Zeroizing::new(fn_pow(&self.d, &SM2_N_MINUS_TWO));
+        PublicShare {
+            point: g_mul_ct(&d_inv),
+        }
+    }
+
+    /// Derive the joint public key `P = [d^-1]peer - G` and turn this partial key
+    /// into a [`KeyShare`]. Both parties end up with the same `P`.
+    pub fn combine(&self, peer: &PublicShare) -> Sm2Result<KeyShare> {
+        let d_inv = Zeroizing::new(fn_pow(&self.d, &SM2_N_MINUS_TWO));
+        let p = peer
+            .point
+            .scalar_mul_ct(&d_inv)
+            .point_add(&g_mul(&SM2_ONE).neg());
+        if p.is_zero() {
+            return Err(Sm2Error::InvalidPublic);
+        }
+        Ok(KeyShare {
+            d: self.d,
+            public_key: Sm2PublicKey {
+                point: p.to_affine_point(),
+            },
+        })
+    }
+}
+
+impl PublicShare {
+    pub fn to_bytes(&self) -> [u8; PUBLIC_SHARE_LEN] {
+        let mut out = [0u8; PUBLIC_SHARE_LEN];
+        out.copy_from_slice(&self.point.to_byte_be(false));
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Sm2Result<Self> {
+        Ok(PublicShare {
+            point: read_point(bytes)?,
+        })
+    }
+}
+
+impl KeyShare {
+    pub fn public_key(&self) -> &Sm2PublicKey {
+        &self.public_key
+    }
+
+    /// Device, round 1: start signing `msg` for the given user ID.
+    pub fn sign_request(
+        &self,
+        id: Option<&[u8]>,
+        msg: &[u8],
+    ) -> Sm2Result<(SignSession, SignRequest)> {
+        self.sign_request_with_rng(&mut rand::thread_rng(), id, msg)
+    }
+
+    pub fn sign_request_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        id: Option<&[u8]>,
+        msg: &[u8],
+    ) -> Sm2Result<(SignSession, SignRequest)> {
+        let mut hasher = self.public_key.za_hasher(id)?;
+        hasher.update(msg);
+        let e = hasher.finalize();
+
+        let k1 = random_u256_with_rng(rng);
+        let request = SignRequest {
+            q1: g_mul_ct(&k1).to_affine_point(),
+            e,
+        };
+        let session = SignSession {
+            d1: self.d,
+            k1,
+            e,
+            public_key: self.public_key,
+        };
+        Ok((session, request))
+    }
+
+    /// Server, round 2: answer a [`SignRequest`] with this party's share.
+    pub fn sign_response(&self, request: &SignRequest) -> Sm2Result<SignResponse> {
+        self.sign_response_with_rng(&mut rand::thread_rng(), request)
+    }
+
+    pub fn sign_response_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        request: &SignRequest,
+    ) -> Sm2Result<SignResponse> {
+        let e = u256_from_be_bytes(&request.e);
+        loop {
+            let k2 = Zeroizing::new(random_u256_with_rng(rng));
+            let k3 = Zeroizing::new(random_u256_with_rng(rng));
+            let p = request
+                .q1
+                .scalar_mul_ct(&k3)
+                .point_add(&g_mul_ct(&k2))
+                .to_affine_point();
+            let x1 = fp_from_mont(&p.x);
+            let r = fn_add(&e, &x1);
+            if r.is_zero() {
+                continue;
+            }
+            return Ok(SignResponse {
+                r,
+                s2: fn_mul(&self.d, &k3),
+                s3: fn_mul(&self.d, &fn_add(&r, &k2)),
+            });
+        }
+    }
+}
+
+impl KeyShare {
+    /// Device, round 1 of decryption: extract `C1` from `ciphertext`.
+    pub fn decrypt_request(
+        &self,
+        ciphertext: &[u8],
+        compressed: bool,
+        model: Sm2Model,
+    ) -> Sm2Result<DecryptRequest> {
+        let (c1, _, _) = split_ciphertext(ciphertext, compressed, model)?;
+        Ok(DecryptRequest {
+            c1: c1.to_affine_point(),
+        })
+    }
+
+    /// Server, round 2 of decryption: the partial point `[d2^-1]C1`.
+    pub fn decrypt_response(&self, request: &DecryptRequest) -> Sm2Result<DecryptResponse> {
+        let d_inv = Zeroizing::new(fn_pow(&self.d, &SM2_N_MINUS_TWO));
+        Ok(DecryptResponse {
+            t1: request.c1.scalar_mul_ct(&d_inv).to_affine_point(),
+        })
+    }
+
+    /// Device, round 3 of decryption: finish with the server's partial point.
+    /// The plaintext is the same as
+    /// [`Sm2PrivateKey::decrypt`](crate::key::Sm2PrivateKey::decrypt) would return.
+    pub fn decrypt_finish(
+        &self,
+        ciphertext: &[u8],
+        compressed: bool,
+        model: Sm2Model,
+        response: &DecryptResponse,
+    ) -> Sm2Result<Vec<u8>> {
+        let (c1, c2_bytes, c3_bytes) = split_ciphertext(ciphertext, compressed, model)?;
+        let d_inv = Zeroizing::new(fn_pow(&self.d, &SM2_N_MINUS_TWO));
+        let mut point = response.t1.scalar_mul_ct(&d_inv).point_add(&c1.neg());
+        if point.is_zero() {
+            return Err(Sm2Error::ZeroPoint);
+        }
+        point = point.to_affine_point();
+        let plain = decrypt_with_point(&point, c2_bytes, c3_bytes);
+        point.zeroize();
+        plain
+    }
+}
+
+impl SignSession {
+    /// Device, round 3: combine the server response into the 64-byte `r || s`
+    /// signature. The result is verified against the joint public key before
+    /// it is returned; on [`Sm2Error::ZeroSig`] start a new session.
+    pub fn finish(self, response: &SignResponse) -> Sm2Result<Vec<u8>> {
+        let r = response.r;
+        let d1_k1 = Zeroizing::new(fn_mul(&self.d1, &self.k1));
+        let s = fn_sub(
+            &fn_add(
+                &fn_mul(&d1_k1, &response.s2),
+                &fn_mul(&self.d1, &response.s3),
+            ),
+            &r,
+        );
+        if s.is_zero() || u256_add(&r, &s).0 == SM2_N {
+            return Err(Sm2Error::ZeroSig);
+        }
+        let mut sig = Vec::with_capacity(64);
+        sig.extend_from_slice(&r.to_byte_be());
+        sig.extend_from_slice(&s.to_byte_be());
+        self.public_key.verify_digest(&self.e, &sig)?;
+        Ok(sig)
+    }
+}
+
+impl SignRequest {
+    pub fn digest(&self) -> &[u8; 32] {
+        &self.e
+    }
+
+    pub fn to_bytes(&self) -> [u8; SIGN_REQUEST_LEN] {
+        let mut out = [0u8; SIGN_REQUEST_LEN];
+        out[..65].copy_from_slice(&self.q1.to_byte_be(false));
+        out[65..].copy_from_slice(&self.e);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Sm2Result<Self> {
+        if bytes.len() != SIGN_REQUEST_LEN {
+            return Err(Sm2Error::InvalidMessage);
+        }
+        let mut e = [0u8; 32];
+        e.copy_from_slice(&bytes[65..]);
+        Ok(SignRequest {
+            q1: read_point(&bytes[..65])?,
+            e,
+        })
+    }
+}
+
+impl SignResponse {
+    pub fn to_bytes(&self) -> [u8; SIGN_RESPONSE_LEN] {
+        let mut out = [0u8; SIGN_RESPONSE_LEN];
+        out[..32].copy_from_slice(&self.r.to_byte_be());
+        out[32..64].copy_from_slice(&self.s2.to_byte_be());
+        out[64..].copy_from_slice(&self.s3.to_byte_be());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Sm2Result<Self> {
+        if bytes.len() != SIGN_RESPONSE_LEN {
+            return Err(Sm2Error::InvalidMessage);
+        }
+        let r = read_scalar(&bytes[..32])?;
+        let s2 = read_scalar(&bytes[32..64])?;
+        let s3 = read_scalar(&bytes[64..])?;
+        if r.is_zero() || s2.is_zero() {
+            return Err(Sm2Error::InvalidMessage);
+        }
+        Ok(SignResponse { r, s2, s3 })
+    }
+}
+
+impl DecryptRequest {
+    pub fn to_bytes(&self) -> [u8; DECRYPT_MESSAGE_LEN] {
+        let mut out = [0u8; DECRYPT_MESSAGE_LEN];
+        out.copy_from_slice(&self.c1.to_byte_be(false));
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Sm2Result<Self> {
+        Ok(DecryptRequest {
+            c1: read_point(bytes)?,
+        })
+    }
+}
+
+impl DecryptResponse {
+    pub fn to_bytes(&self) -> [u8; DECRYPT_MESSAGE_LEN] {
+        let mut out = [0u8; DECRYPT_MESSAGE_LEN];
+        out.copy_from_slice(&self.t1.to_byte_be(false));
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Sm2Result<Self> {
+        Ok(DecryptResponse {
+            t1: read_point(bytes)?,
+        })
+    }
+}
+
+/// An uncompressed point that must lie on the curve.
+pub(crate) fn read_point(bytes: &[u8]) -> Sm2Result<Point> {
+    if bytes.len() != 65 || bytes[0] != 0x04 {
+        return Err(Sm2Error::InvalidMessage);
+    }
+    if u256_cmp(&u256_from_be_bytes(&bytes[1..33]), &SM2_P) >= 0
+        || u256_cmp(&u256_from_be_bytes(&bytes[33..]), &SM2_P) >= 0
+    {
+        return Err(Sm2Error::NotOnCurve);
+    }
+    let point = Point::from_byte(bytes)?;
+    if !point.is_valid_affine_point() {
+        return Err(Sm2Error::NotOnCurve);
+    }
+    Ok(point)
+}
+
+/// A 32-byte big-endian scalar that must be below `n`.
+pub(crate) fn read_scalar(bytes: &[u8]) -> Sm2Result<U256> {
+    let a = u256_from_be_bytes(bytes);
+    if u256_cmp(&a, &SM2_N) >= 0 {
+        return Err(Sm2Error::InvalidMessage);
+    }
+    Ok(a)
+}
+
+impl fmt::Debug for PartialKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PartialKey").finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for KeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyShare")
+            .field("public_key", &self.public_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for SignSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignSession")
+            .field("e", &self.e)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Zeroize for PartialKey {
+    fn zeroize(&mut self) {
+        self.d.zeroize();
+    }
+}
+
+impl Drop for PartialKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for PartialKey {}
+
+impl Zeroize for KeyShare {
+    fn zeroize(&mut self) {
+        self.d.zeroize();
+    }
+}
+
+impl Drop for KeyShare {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for KeyShare {}
+
+impl Zeroize for SignSession {
+    fn zeroize(&mut self) {
+        self.d1.zeroize();
+        self.k1.zeroize();
+    }
+}
+
+impl Drop for SignSession {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SignSession {}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::collab::{
+        DecryptRequest, DecryptResponse, KeyShare, PartialKey, PublicShare, SignRequest,
+        SignResponse,
+    };
+    use crate::error::Sm2Error;
+    use crate::fields::fn64::{fn_mul, fn_pow, fn_sub, SM2_N_MINUS_TWO};
+    use crate::fields::FieldModOperation;
+    use crate::key::{Sm2Model, Sm2PrivateKey};
+    use crate::u256::SM2_ONE;
+
+    fn keygen() -> (KeyShare, KeyShare) {
+        let device = PartialKey::generate();
+        let server = PartialKey::generate();
+        // only the encoded public shares cross the wire
+        let device_msg = device.public_share().to_bytes();
+        let server_msg = server.public_share().to_bytes();
+        let device = device
+            .combine(&PublicShare::from_bytes(&server_msg).unwrap())
+            .unwrap();
+        let server = server
+            .combine(&PublicShare::from_bytes(&device_msg).unwrap())
+            .unwrap();
+        (device, server)
+    }
+
+    #[test]
+    fn test_co_sign() {
+        let (device, server) = keygen();
+        assert_eq!(
+            device.public_key().to_bytes(false),
+            server.public_key().to_bytes(false)
+        );
+        let pk = *device.public_key();
+        assert!(pk.is_valid());
+
+        for msg in [&b"hello world"[..], b"", &[0xab; 1000]] {
+            let (session, request) = device.sign_request(Some(b"alice"), msg).unwrap();
+            let request = SignRequest::from_bytes(&request.to_bytes()).unwrap();
+            let response = server.sign_response(&request).unwrap();
+            let response = SignResponse::from_bytes(&response.to_bytes()).unwrap();
+            let sig = session.finish(&response).unwrap();
+            pk.verify(Some(b"alice"), msg, &sig).unwrap();
+            assert!(pk.verify(None, msg, &sig).is_err());
+        }
+    }
+
+    #[test]
+    fn test_co_sign_seeded() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let device_partial = PartialKey::generate_with_rng(&mut rng);
+        let server_partial = PartialKey::generate_with_rng(&mut rng);
+        let device = device_partial
+            .combine(&server_partial.public_share())
+            .unwrap();
+        let server = server_partial
+            .combine(&device_partial.public_share())
+            .unwrap();
+
+        let (session, request) = device
+            .sign_request_with_rng(&mut rng, None, b"abc")
+            .unwrap();
+        let response = server.sign_response_with_rng(&mut rng, &request).unwrap();
+        let sig = session.finish(&response).unwrap();
+        device.public_key().verify(None, b"abc", &sig).unwrap();
+    }
+
+    #[test]
+    fn test_co_sign_rejects_bad_response() {
+        let (device, server) = keygen();
+        let (session, request) = device.sign_request(None, b"abc").unwrap();
+        let response = server.sign_response(&request).unwrap();
+        let mut bytes = response.to_bytes();
+        bytes[95] ^= 1;
+        let response = SignResponse::from_bytes(&bytes).unwrap();
+        assert!(session.finish(&response).is_err());
+
+        // a share that does not belong to this key cannot help
+        let (_, other) = keygen();
+        let (session, request) = device.sign_request(None, b"abc").unwrap();
+        let response = other.sign_response(&request).unwrap();
+        assert!(session.finish(&response).is_err());
+    }
+
+    // d = (d1 * d2)^-1 - 1, only ever rebuilt here to compare against
+    fn joint_private_key(device: &KeyShare, server: &KeyShare) -> Sm2PrivateKey {
+        let d = fn_sub(
+            &fn_pow(&fn_mul(&device.d, &server.d), &SM2_N_MINUS_TWO),
+            &SM2_ONE,
+        );
+        Sm2PrivateKey::new(&d.to_byte_be()).unwrap()
+    }
+
+    fn co_decrypt(
+        device: &KeyShare,
+        server: &KeyShare,
+        ciphertext: &[u8],
+        compressed: bool,
+        model: fn() -> Sm2Model,
+    ) -> Result<Vec<u8>, Sm2Error> {
+        let request = device.decrypt_request(ciphertext, compressed, model())?;
+        let request = DecryptRequest::from_bytes(&request.to_bytes())?;
+        let response = server.decrypt_response(&request)?;
+        let response = DecryptResponse::from_bytes(&response.to_bytes())?;
+        device.decrypt_finish(ciphertext, compressed, model(), &response)
+    }
+
+    #[test]
+    fn test_co_decrypt() {
+        let (device, server) = keygen();
+        let sk = joint_private_key(&device, &server);
+        assert_eq!(
+            sk.public_key.to_bytes(false),
+            device.public_key().to_bytes(false)
+        );
+        let pk = *device.public_key();
+
+        let models: [fn() -> Sm2Model; 2] = [|| Sm2Model::C1C3C2, || Sm2Model::C1C2C3];
+        for msg in [&b"a"[..], b"hello world", &[0x5a; 300]] {
+            for compressed in [false, true] {
+                for model in models {
+                    let ciphertext = pk.encrypt(msg, compressed, model()).unwrap();
+                    let plain = co_decrypt(&device, &server, &ciphertext, compressed, model);
+                    assert_eq!(plain.as_deref(), Ok(msg));
+                    assert_eq!(plain, sk.decrypt(&ciphertext, compressed, model()));
+                }
+            }
+        }
+
+        // a tampered C2 fails the same way as the single-key decryption
+        let mut ciphertext = pk.encrypt(b"hello", false, Sm2Model::C1C3C2).unwrap();
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 1;
+        let plain = co_decrypt(&device, &server, &ciphertext, false, || Sm2Model::C1C3C2);
+        assert_eq!(plain, Err(Sm2Error::HashNotEqual));
+        assert_eq!(plain, sk.decrypt(&ciphertext, false, Sm2Model::C1C3C2));
+
+        // the wrong server share does not decrypt
+        let (_, other) = keygen();
+        let ciphertext = pk.encrypt(b"hello", false, Sm2Model::C1C3C2).unwrap();
+        let plain = co_decrypt(&device, &other, &ciphertext, false, || Sm2Model::C1C3C2);
+        assert!(plain.is_err());
+    }
+
+    #[test]
+    fn test_message_decoding() {
+        assert_eq!(
+            PublicShare::from_bytes(&[]).unwrap_err(),
+            Sm2Error::InvalidMessage
+        );
+        let mut point = [0u8; 65];
+        point[0] = 0x04;
+        point[64] = 1;
+        assert_eq!(
+            PublicShare::from_bytes(&point).unwrap_err(),
+            Sm2Error::NotOnCurve
+        );
+        assert!(SignRequest::from_bytes(&[0u8; 96]).is_err());
+        assert_eq!(
+            SignResponse::from_bytes(&[0xff; 96]).unwrap_err(),
+            Sm2Error::InvalidMessage
+        );
+        assert_eq!(
+            SignResponse::from_bytes(&[0u8; 96]).unwrap_err(),
+            Sm2Error::InvalidMessage
+        );
+    }
+}
@@ -0,0 +1,418 @@
+//! Digital envelopes: SM2 key transport around SM4 encrypted content.
+//!
+//! [`seal`] draws a fresh 16-byte SM4 key, encrypts the content with it and
+//! encrypts the key to every recipient with [`Sm2PublicKey::encrypt_der`]. The
+//! result is a GM/T 0010 `ContentInfo` of type [`OID_SM2_CMS_ENVELOPED`]:
+//!
+//! ```text
+//! EnvelopedData ::= SEQUENCE {
+//!     version               INTEGER,          -- 0, or 2 with key identifiers
+//!     recipientInfos        SET OF KeyTransRecipientInfo,
+//!     encryptedContentInfo  SEQUENCE {
+//!         contentType                 OBJECT IDENTIFIER,  -- OID_SM2_CMS_DATA
+//!         contentEncryptionAlgorithm  AlgorithmIdentifier,
+//!         encryptedContent        [0] IMPLICIT OCTET STRING } }
+//! ```
+//!
+//! Each `KeyTransRecipientInfo` carries the key encryption algorithm
+//! [`OID_SM2_CMS_3`] and the DER `SM2Cipher` of the content key. SM4-CBC and
+//! SM4-CTR take the 16-byte IV as an OCTET STRING parameter. GM/T 0010 does not
+//! define SM4-GCM; it is encoded as in RFC 5084, with `GCMParameters` holding
+//! the 12-byte nonce and a 16-byte tag appended to the encrypted content.
+//!
+//! # Example
+//! ```rust
+//! use gm_sm2::envelope::{open, seal, ContentEncryption, Recipient};
+//! use gm_sm2::key::gen_keypair;
+//! fn main(){
+//!     let (alice_pk, alice_sk) = gen_keypair().unwrap();
+//!     let (bob_pk, bob_sk) = gen_keypair().unwrap();
+//!     let recipients = [
+//!         Recipient::with_key_id(alice_pk, b"alice").unwrap(),
+//!         Recipient::with_key_id(bob_pk, b"bob").unwrap(),
+//!     ];
+//!     let envelope = seal(&recipients, b"quarterly report", ContentEncryption::Sm4Gcm).unwrap();
+//!     assert_eq!(b"quarterly report".to_vec(), open(&envelope, &alice_sk).unwrap());
+//!     assert_eq!(b"quarterly report".to_vec(), open(&envelope, &bob_sk).unwrap());
+//! }
+//! ```
+//!
+//! [`Sm2PublicKey::encrypt_der`]: crate::key::Sm2PublicKey::encrypt_der
+
+use cms::cert::x509::ext::pkix::SubjectKeyIdentifier;
+use cms::cert::x509::spki::AlgorithmIdentifierOwned;
+use cms::content_info::{CmsVersion, ContentInfo};
+use cms::enveloped_data::{
+    EncryptedContentInfo, EnvelopedData, KeyTransRecipientInfo, RecipientIdentifier, RecipientInfo,
+    RecipientInfos,
+};
+use gm_sm4::gcm::Sm4Gcm;
+use gm_sm4::{CipherMode, Sm4CipherMode, OID_SM4_CBC, OID_SM4_CTR, OID_SM4_GCM};
+use pkcs8::der::asn1::{OctetString, SetOfVec};
+use pkcs8::der::{Any, Decode, Encode};
+use rand::{CryptoRng, RngCore};
+use zeroize::Zeroizing;
+
+use crate::error::{Sm2Error, Sm2Result};
+use crate::key::{Sm2PrivateKey, Sm2PublicKey};
+use crate::{OID_SM2_CMS_3, OID_SM2_CMS_DATA, OID_SM2_CMS_ENVELOPED, OID_SM2_PKCS8};
+
+const SM4_KEY_LEN: usize = 16;
+const GCM_NONCE_LEN: usize = 12;
+const GCM_TAG_LEN: usize = 16;
+
+/// Symmetric algorithm protecting the content of an envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncryption {
+    /// SM4-CBC with PKCS#7 padding, `1.2.156.10197.1.104.2`.
+    Sm4Cbc,
+    /// SM4-CTR, `1.2.156.10197.1.104.7`.
+    Sm4Ctr,
+    /// SM4-GCM with a 16-byte tag, `1.2.156.10197.1.104.8`.
+    Sm4Gcm,
+}
+
+/// A recipient of an envelope: the SM2 key the content key is encrypted to and
+/// the identifier stored next to it.
+#[derive(Debug, Clone)]
+pub struct Recipient {
+    public_key: Sm2PublicKey,
+    rid: RecipientIdentifier,
+}
+
+impl Recipient {
+    /// Use any CMS recipient identifier, typically the issuer and serial
+    /// number of the recipient's certificate.
+    pub fn new(public_key: Sm2PublicKey, rid: RecipientIdentifier) -> Recipient {
+        Recipient { public_key, rid }
+    }
+
+    /// Identify the recipient by a subject key identifier.
+    pub fn with_key_id(public_key: Sm2PublicKey, key_id: &[u8]) -> Sm2Result<Recipient> {
+        let key_id = OctetString::new(key_id).map_err(|_| Sm2Error::InvalidDer)?;
+        Ok(Recipient {
+            public_key,
+            rid: RecipientIdentifier::SubjectKeyIdentifier(SubjectKeyIdentifier(key_id)),
+        })
+    }
+}
+
+/// Encrypt `content` for all `recipients` and return the DER `ContentInfo`.
+pub fn seal(
+    recipients: &[Recipient],
+    content: &[u8],
+    alg: ContentEncryption,
+) -> Sm2Result<Vec<u8>> {
+    seal_with_rng(&mut rand::thread_rng(), recipients, content, alg)
+}
+
+/// Like [`seal`], drawing the content key, the IV and the SM2 ephemeral keys
+/// from `rng`.
+pub fn seal_with_rng<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    recipients: &[Recipient],
+    content: &[u8],
+    alg: ContentEncryption,
+) -> Sm2Result<Vec<u8>> {
+    if recipients.is_empty() {
+        return Err(Sm2Error::NoRecipient);
+    }
+    let mut key = Zeroizing::new([0u8; SM4_KEY_LEN]);
+    rng.fill_bytes(&mut key[..]);
+
+    let mut infos = vec![];
+    let mut version = CmsVersion::V0;
+    for recipient in recipients {
+        let enc_key = recipient.public_key.encrypt_der_with_rng(rng, &key[..])?;
+        let ktri_version = match recipient.rid {
+            RecipientIdentifier::IssuerAndSerialNumber(_) => CmsVersion::V0,
+            RecipientIdentifier::SubjectKeyIdentifier(_) => CmsVersion::V2,
+        };
+        if ktri_version == CmsVersion::V2 {
+            version = CmsVersion::V2;
+        }
+        infos.push(RecipientInfo::Ktri(KeyTransRecipientInfo {
+            version: ktri_version,
+            rid: recipient.rid.clone(),
+            key_enc_alg: AlgorithmIdentifierOwned {
+                oid: OID_SM2_CMS_3,
+                parameters: None,
+            },
+            enc_key: OctetString::new(enc_key).map_err(|_| Sm2Error::InvalidDer)?,
+        }));
+    }
+
+    let (content_enc_alg, encrypted) = encrypt_content(rng, &key[..], content, alg)?;
+    let enveloped = EnvelopedData {
+        version,
+        originator_info: None,
+        recip_infos: RecipientInfos(SetOfVec::try_from(infos).map_err(|_| Sm2Error::InvalidDer)?),
+        encrypted_content: EncryptedContentInfo {
+            content_type: OID_SM2_CMS_DATA,
+            content_enc_alg,
+            encrypted_content: Some(OctetString::new(encrypted).map_err(|_| Sm2Error::InvalidDer)?),
+        },
+        unprotected_attrs: None,
+    };
+    ContentInfo {
+        content_type: OID_SM2_CMS_ENVELOPED,
+        content: Any::encode_from(&enveloped).map_err(|_| Sm2Error::InvalidDer)?,
+    }
+    .to_der()
+    .map_err(|_| Sm2Error::InvalidDer)
+}
+
+/// Decrypt an envelope produced by [`seal`] or another GM/T 0010 implementation.
+///
+/// Every SM2 key transport recipient is tried in turn, so opening costs up to
+/// one SM2 decryption per recipient. Returns [`Sm2Error::NoRecipient`] if none
+/// of them was encrypted to `sk`.
+pub fn open(envelope: &[u8], sk: &Sm2PrivateKey) -> Sm2Result<Vec<u8>> {
+    let content_info = ContentInfo::from_der(envelope).map_err(|_| Sm2Error::InvalidDer)?;
+    if content_info.content_type != OID_SM2_CMS_ENVELOPED {
+        return Err(Sm2Error::UnsupportedAlgorithm);
+    }
+    let enveloped: EnvelopedData = content_info
+        .content
+        .decode_as()
+        .map_err(|_| Sm2Error::InvalidDer)?;
+
+    let mut key = None;
+    for info in enveloped.recip_infos.0.iter() {
+        let ktri = match info {
+            RecipientInfo::Ktri(ktri) => ktri,
+            _ => continue,
+        };
+        if ktri.key_enc_alg.oid != OID_SM2_CMS_3 && ktri.key_enc_alg.oid != OID_SM2_PKCS8 {
+            continue;
+        }
+        if let Ok(k) = sk.decrypt_der(ktri.enc_key.as_bytes()) {
+            key = Some(Zeroizing::new(k));
+            break;
+        }
+    }
+    let key = key.ok_or(Sm2Error::NoRecipient)?;
+    if key.len() != SM4_KEY_LEN {
+        return Err(Sm2Error::InvalidSecretKey);
+    }
+
+    let info = &enveloped.encrypted_content;
+    let encrypted = info
+        .encrypted_content
+        .as_ref()
+        .ok_or(Sm2Error::InvalidDer)?
+        .as_bytes();
+    decrypt_content(&key, &info.content_enc_alg, encrypted)
+}
+
+fn encrypt_content<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    key: &[u8],
+    content: &[u8],
+    alg: ContentEncryption,
+) -> Sm2Result<(AlgorithmIdentifierOwned, Vec<u8>)> {
+    let (oid, parameters, encrypted) = match alg {
+        ContentEncryption::Sm4Cbc | ContentEncryption::Sm4Ctr => {
+            let (oid, mode) = match alg {
+                ContentEncryption::Sm4Cbc => (OID_SM4_CBC, CipherMode::Cbc),
+                _ => (OID_SM4_CTR, CipherMode::Ctr),
+            };
+            let mut iv = [0u8; 16];
+            rng.fill_bytes(&mut iv);
+            let encrypted = Sm4CipherMode::new(key, mode)
+                .and_then(|cipher| cipher.encrypt(content, &iv))
+                .map_err(|_| Sm2Error::InvalidSecretKey)?;
+            let parameters =
+                Any::encode_from(&OctetString::new(iv).map_err(|_| Sm2Error::InvalidDer)?);
+            (oid, parameters, encrypted)
+        }
+        ContentEncryption::Sm4Gcm => {
+            let mut nonce = [0u8; GCM_NONCE_LEN];
+            rng.fill_bytes(&mut nonce);
+            let encrypted = Sm4Gcm::new(key)
+                .and_then(|gcm| gcm.encrypt(&nonce, &[], content))
+                .map_err(|_| Sm2Error::InvalidSecretKey)?;
+            // GCMParameters ::= SEQUENCE { nonce OCTET STRING, icvLen INTEGER DEFAULT 12 }
+            let params = yasna::construct_der(|writer| {
+                writer.write_sequence(|writer| {
+                    writer.next().write_bytes(&nonce);
+                    writer.next().write_u8(GCM_TAG_LEN as u8);
+                })
+            });
+            (OID_SM4_GCM, Any::from_der(&params), encrypted)
+        }
+    };
+    let parameters = parameters.map_err(|_| Sm2Error::InvalidDer)?;
+    Ok((
+        AlgorithmIdentifierOwned {
+            oid,
+            parameters: Some(parameters),
+        },
+        encrypted,
+    ))
+}
+
+fn decrypt_content(
+    key: &[u8],
+    alg: &AlgorithmIdentifierOwned,
+    encrypted: &[u8],
+) -> Sm2Result<Vec<u8>> {
+    let parameters = alg
+        .parameters
+        .as_ref()
+        .ok_or(Sm2Error::InvalidDer)?
+        .to_der()
+        .map_err(|_| Sm2Error::InvalidDer)?;
+    if alg.oid == OID_SM4_CBC || alg.oid == OID_SM4_CTR {
+        let iv = OctetString::from_der(&parameters).map_err(|_| Sm2Error::InvalidDer)?;
+        if iv.as_bytes().len() != 16 {
+            return Err(Sm2Error::InvalidDer);
+        }
+        let mode = if alg.oid == OID_SM4_CBC {
+            // a padded CBC body is at least one block; gm-sm4 rejects a
+            // partial block but not an empty body
+            if encrypted.is_empty() {
+                return Err(Sm2Error::InvalidCiphertext);
+            }
+            CipherMode::Cbc
+        } else {
+            CipherMode::Ctr
+        };
+        Sm4CipherMode::new(key, mode)
+            .and_then(|cipher| cipher.decrypt(encrypted, iv.as_bytes()))
+            .map_err(|_| Sm2Error::InvalidCiphertext)
+    } else if alg.oid == OID_SM4_GCM {
+        let (nonce, tag_len) = yasna::parse_der(&parameters, |reader| {
+            reader.read_sequence(|reader| {
+                let nonce = reader.next().read_bytes()?;
+                let tag_len = reader.read_optional(|reader| reader.read_u8())?;
+                Ok((nonce, tag_len.unwrap_or(12)))
+            })
+        })
+        .map_err(|_| Sm2Error::InvalidDer)?;
+        if tag_len as usize != GCM_TAG_LEN {
+            return Err(Sm2Error::UnsupportedAlgorithm);
+        }
+        Sm4Gcm::new(key)
+            .and_then(|gcm| gcm.decrypt(&nonce, &[], encrypted))
+            .map_err(|_| Sm2Error::InvalidCiphertext)
+    } else {
+        Err(Sm2Error::UnsupportedAlgorithm)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cms::cert::x509::name::Name;
+    use cms::cert::x509::serial_number::SerialNumber;
+    use cms::cert::IssuerAndSerialNumber;
+    use cms::content_info::{CmsVersion, ContentInfo};
+    use cms::enveloped_data::{EnvelopedData, RecipientIdentifier};
+    use pkcs8::der::{Decode, Encode};
+
+    use crate::envelope::{open, seal, ContentEncryption, Recipient};
+    use crate::error::Sm2Error;
+    use crate::key::gen_keypair;
+    use crate::OID_SM2_CMS_ENVELOPED;
+
+    fn parse(envelope: &[u8]) -> EnvelopedData {
+        let content_info = ContentInfo::from_der(envelope).unwrap();
+        assert_eq!(OID_SM2_CMS_ENVELOPED, content_info.content_type);
+        content_info.content.decode_as().unwrap()
+    }
+
+    fn with_body(envelope: &[u8], f: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+        let mut enveloped = parse(envelope);
+        let mut body = enveloped
+            .encrypted_content
+            .encrypted_content
+            .as_ref()
+            .unwrap()
+            .as_bytes()
+            .to_vec();
+        f(&mut body);
+        enveloped.encrypted_content.encrypted_content =
+            Some(pkcs8::der::asn1::OctetString::new(body).unwrap());
+        ContentInfo {
+            content_type: OID_SM2_CMS_ENVELOPED,
+            content: pkcs8::der::Any::encode_from(&enveloped).unwrap(),
+        }
+        .to_der()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_envelope() {
+        let (pk_a, sk_a) = gen_keypair().unwrap();
+        let (pk_b, sk_b) = gen_keypair().unwrap();
+        let (_, sk_c) = gen_keypair().unwrap();
+        let recipients = [
+            Recipient::with_key_id(pk_a, b"a").unwrap(),
+            Recipient::with_key_id(pk_b, b"b").unwrap(),
+        ];
+        for alg in [
+            ContentEncryption::Sm4Cbc,
+            ContentEncryption::Sm4Ctr,
+            ContentEncryption::Sm4Gcm,
+        ] {
+            for len in [0, 1, 16, 100] {
+                let msg = vec![0x3c; len];
+                let envelope = seal(&recipients, &msg, alg).unwrap();
+                assert_eq!(2, parse(&envelope).recip_infos.0.len());
+                assert_eq!(CmsVersion::V2, parse(&envelope).version);
+                assert_eq!(msg, open(&envelope, &sk_a).unwrap());
+                assert_eq!(msg, open(&envelope, &sk_b).unwrap());
+                assert_eq!(Sm2Error::NoRecipient, open(&envelope, &sk_c).unwrap_err());
+            }
+        }
+        assert_eq!(
+            Sm2Error::NoRecipient,
+            seal(&[], b"msg", ContentEncryption::Sm4Cbc).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_envelope_issuer_and_serial() {
+        let (pk, sk) = gen_keypair().unwrap();
+        let rid = RecipientIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+            issuer: Name::default(),
+            serial_number: SerialNumber::new(&[0x01, 0x02]).unwrap(),
+        });
+        let envelope = seal(
+            &[Recipient::new(pk, rid)],
+            b"msg",
+            ContentEncryption::Sm4Cbc,
+        )
+        .unwrap();
+        assert_eq!(CmsVersion::V0, parse(&envelope).version);
+        assert_eq!(b"msg".to_vec(), open(&envelope, &sk).unwrap());
+    }
+
+    #[test]
+    fn test_envelope_tampered() {
+        let (pk, sk) = gen_keypair().unwrap();
+        let recipients = [Recipient::with_key_id(pk, b"key").unwrap()];
+        let envelope = seal(&recipients, b"attack at dawn", ContentEncryption::Sm4Gcm).unwrap();
+
+        let tampered = with_body(&envelope, |body| body[0] ^= 0x01);
+        assert_eq!(
+            Sm2Error::InvalidCiphertext,
+            open(&tampered, &sk).unwrap_err()
+        );
+        // a CBC body that is not a whole number of blocks, or empty
+        let cbc = seal(&recipients, b"attack at dawn", ContentEncryption::Sm4Cbc).unwrap();
+        for truncate in [1, 16] {
+            let tampered = with_body(&cbc, |body| body.truncate(body.len() - truncate));
+            assert_eq!(
+                Sm2Error::InvalidCiphertext,
+                open(&tampered, &sk).unwrap_err()
+            );
+        }
+
+        assert_eq!(
+            Sm2Error::InvalidDer,
+            open(&envelope[..envelope.len() - 1], &sk).unwrap_err()
+        );
+        assert_eq!(Sm2Error::InvalidDer, open(&[], &sk).unwrap_err());
+    }
+}
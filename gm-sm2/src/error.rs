@@ -3,7 +3,7 @@ use std::fmt::Formatter;
 
 pub type Sm2Result<T> = Result<T, Sm2Error>;
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum Sm2Error {
     NotOnCurve,
     FieldSqrtError,
@@ -24,6 +24,17 @@ pub enum Sm2Error {
     InvalidDigest,
     InvalidSecretKey,
     KdfHashError,
+    InvalidSignature,
+    InvalidMessage,
+    InvalidShare,
+    NotEnoughShares,
+    InvalidThreshold,
+    InvalidRecoveryId,
+    InvalidCiphertext,
+    UnsupportedAlgorithm,
+    NoRecipient,
+    NoSigner,
+    ContentMissing,
 }
 
 impl ::std::fmt::Debug for Sm2Error {
@@ -54,10 +65,29 @@ impl From<Sm2Error> for &str {
             Sm2Error::InvalidSecretKey => "invalid secret key",
             Sm2Error::KdfHashError => "KDF hash error",
             Sm2Error::InvalidDigest => "invalid signature digest",
+            Sm2Error::InvalidSignature => "invalid signature, r and s must be in [1, n-1]",
+            Sm2Error::InvalidMessage => "malformed protocol message",
+            Sm2Error::InvalidShare => "the share does not match its commitments",
+            Sm2Error::NotEnoughShares => "not enough parties or shares to continue",
+            Sm2Error::InvalidThreshold => "invalid threshold parameters",
+            Sm2Error::InvalidRecoveryId => "the recovery id does not match any public key",
+            Sm2Error::InvalidCiphertext => "malformed ciphertext",
+            Sm2Error::UnsupportedAlgorithm => "unsupported algorithm identifier",
+            Sm2Error::NoRecipient => "no recipient matches the private key",
+            Sm2Error::NoSigner => "no signer, or no certificate for the signer",
+            Sm2Error::ContentMissing => "the signed content is neither attached nor given",
         }
     }
 }
 
+impl std::error::Error for Sm2Error {}
+
+impl From<Sm2Error> for std::io::Error {
+    fn from(e: Sm2Error) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+    }
+}
+
 impl Display for Sm2Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let err_msg = match self {
@@ -80,6 +110,17 @@ impl Display for Sm2Error {
             Sm2Error::InvalidSecretKey => "invalid secret key",
             Sm2Error::KdfHashError => "KDF hash error",
             Sm2Error::InvalidDigest => "invalid signature digest",
+            Sm2Error::InvalidSignature => "invalid signature, r and s must be in [1, n-1]",
+            Sm2Error::InvalidMessage => "malformed protocol message",
+            Sm2Error::InvalidShare => "the share does not match its commitments",
+            Sm2Error::NotEnoughShares => "not enough parties or shares to continue",
+            Sm2Error::InvalidThreshold => "invalid threshold parameters",
+            Sm2Error::InvalidRecoveryId => "the recovery id does not match any public key",
+            Sm2Error::InvalidCiphertext => "malformed ciphertext",
+            Sm2Error::UnsupportedAlgorithm => "unsupported algorithm identifier",
+            Sm2Error::NoRecipient => "no recipient matches the private key",
+            Sm2Error::NoSigner => "no signer, or no certificate for the signer",
+            Sm2Error::ContentMissing => "the signed content is neither attached nor given",
         };
         write!(f, "{}", err_msg)
     }
@@ -1,17 +1,20 @@
+use std::fmt;
+
 use byteorder::{BigEndian, WriteBytesExt};
+use rand::{CryptoRng, RngCore};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
-use gm_sm3::sm3_hash;
+use gm_sm3::{sm3_hash, sm3_kdf};
 
 use crate::error::{Sm2Error, Sm2Result};
 use crate::fields::FieldModOperation;
 use crate::fields::fn64::{fn_add, fn_mul};
-use crate::fields::fp64::{fp_from_mont, random_u256};
-use crate::key::{gen_keypair, Sm2PrivateKey, Sm2PublicKey};
-use crate::p256_ecc::{g_mul, Point};
+use crate::fields::fp64::{fp_from_mont, random_u256_with_rng};
+use crate::key::{gen_keypair_with_rng, Sm2PrivateKey, Sm2PublicKey};
+use crate::p256_ecc::{g_mul_ct, Point};
 use crate::u256::{SM2_ONE, U256, u256_add, u256_bits_and, u256_sub};
-use crate::util::{compute_za, DEFAULT_ID, kdf};
+use crate::util::{compute_za, DEFAULT_ID};
 
-#[derive(Debug)]
 pub struct Exchange {
     klen: usize,
     za: [u8; 32],
@@ -25,15 +28,57 @@ pub struct Exchange {
     rhs_pk: Sm2PublicKey,
 }
 
+impl fmt::Debug for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Exchange")
+            .field("klen", &self.klen)
+            .field("za", &self.za)
+            .field("r_point", &self.r_point)
+            .field("rhs_za", &self.rhs_za)
+            .field("rhs_pk", &self.rhs_pk)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Clears the private key, the ephemeral `r`, the shared point `V`/`U` and the
+/// agreed key.
+impl Zeroize for Exchange {
+    fn zeroize(&mut self) {
+        self.sk.zeroize();
+        self.v.zeroize();
+        self.r.zeroize();
+        self.k.zeroize();
+    }
+}
+
+impl Drop for Exchange {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Exchange {}
+
 /// Build the exchange Pair
 ///
 pub fn build_ex_pair(
     klen: usize,
-    first_id: &str,
-    other_id: &str,
+    first_id: &[u8],
+    other_id: &[u8],
+) -> Sm2Result<(Exchange, Exchange)> {
+    build_ex_pair_with_rng(&mut rand::thread_rng(), klen, first_id, other_id)
+}
+
+/// Build the exchange Pair with both key pairs drawn from `rng`
+///
+pub fn build_ex_pair_with_rng<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    klen: usize,
+    first_id: &[u8],
+    other_id: &[u8],
 ) -> Sm2Result<(Exchange, Exchange)> {
-    let (pk_a, sk_a) = gen_keypair().unwrap();
-    let (pk_b, sk_b) = gen_keypair().unwrap();
+    let (pk_a, sk_a) = gen_keypair_with_rng(rng).unwrap();
+    let (pk_b, sk_b) = gen_keypair_with_rng(rng).unwrap();
     let user_a = Exchange::new(klen, Some(first_id), &pk_a, &sk_a, Some(other_id), &pk_b).unwrap();
     let user_b = Exchange::new(klen, Some(other_id), &pk_b, &sk_b, Some(first_id), &pk_a).unwrap();
     Ok((user_a, user_b))
@@ -42,10 +87,10 @@ pub fn build_ex_pair(
 impl Exchange {
     pub fn new(
         klen: usize,
-        id: Option<&str>,
+        id: Option<&[u8]>,
         pk: &Sm2PublicKey,
         sk: &Sm2PrivateKey,
-        rhs_id: Option<&str>,
+        rhs_id: Option<&[u8]>,
         rhs_pk: &Sm2PublicKey,
     ) -> Sm2Result<Exchange> {
         let id = id.unwrap_or_else(|| DEFAULT_ID);
@@ -68,8 +113,12 @@ impl Exchange {
     // A2：计算椭圆曲线点RA = [rA]G=(x1,y1)；
     // A3：将RA发送给用户B；
     pub fn exchange_1(&mut self) -> Sm2Result<Point> {
-        let r = random_u256();
-        let r_point = g_mul(&r);
+        self.exchange_1_with_rng(&mut rand::thread_rng())
+    }
+
+    pub fn exchange_1_with_rng<R: CryptoRng + RngCore>(&mut self, rng: &mut R) -> Sm2Result<Point> {
+        let r = random_u256_with_rng(rng);
+        let r_point = g_mul_ct(&r);
         self.r = Some(r);
         self.r_point = Some(r_point);
         Ok(r_point)
@@ -78,6 +127,14 @@ impl Exchange {
     // Step2: UserB Call
     //
     pub fn exchange_2(&mut self, ra_point: &Point) -> Sm2Result<(Point, [u8; 32])> {
+        self.exchange_2_with_rng(&mut rand::thread_rng(), ra_point)
+    }
+
+    pub fn exchange_2_with_rng<R: CryptoRng + RngCore>(
+        &mut self,
+        rng: &mut R,
+        ra_point: &Point,
+    ) -> Sm2Result<(Point, [u8; 32])> {
         if !ra_point.is_valid() {
             return Err(Sm2Error::CheckPointErr);
         }
@@ -89,21 +146,21 @@ impl Exchange {
             0x0000000000000000,
         ];
 
-        let r2 = random_u256();
-        let r2_point = g_mul(&r2);
+        let r2 = random_u256_with_rng(rng);
+        let r2_point = g_mul_ct(&r2);
         self.r = Some(r2);
         self.r_point = Some(r2_point);
         let r2_point_affine = r2_point.to_affine_point();
         let x2 = fp_from_mont(&r2_point_affine.x);
         let y2 = fp_from_mont(&r2_point_affine.y);
         let x2_b = u256_add(&pow, &u256_bits_and(&x2, &u256_sub(&pow, &SM2_ONE).0)).0;
-        let t2 = fn_add(
+        let t2 = Zeroizing::new(fn_add(
             &self.sk.d,
             &fn_mul(
                 &self.r.as_ref().unwrap(),
                 &x2_b,
             ),
-        );
+        ));
 
         let ra_point_affine = ra_point.to_affine_point();
         let x1 = fp_from_mont(&ra_point_affine.x);
@@ -114,26 +171,26 @@ impl Exchange {
             .rhs_pk
             .value()
             .point_add(&ra_point.scalar_mul(&x1_a));
-        let v_point = p.scalar_mul(&t2);
+        let v_point = p.scalar_mul_ct(&t2);
         if v_point.is_zero() {
             return Err(Sm2Error::ZeroPoint);
         }
         self.v = Some(v_point);
 
         let v_affine_p = v_point.to_affine_point();
-        let xv_bytes = fp_from_mont(&v_affine_p.x).to_byte_be();
-        let yv_bytes = fp_from_mont(&v_affine_p.y).to_byte_be();
+        let xv_bytes = Zeroizing::new(fp_from_mont(&v_affine_p.x).to_byte_be());
+        let yv_bytes = Zeroizing::new(fp_from_mont(&v_affine_p.y).to_byte_be());
 
-        let mut prepend = Vec::new();
+        let mut prepend = Zeroizing::new(Vec::new());
         prepend.extend_from_slice(&xv_bytes);
         prepend.extend_from_slice(&yv_bytes);
         prepend.extend_from_slice(&self.rhs_za); // User A
         prepend.extend_from_slice(&self.za); // User B
 
-        let k_b = kdf(&prepend, self.klen);
+        let k_b = sm3_kdf(&prepend, self.klen);
         self.k = Some(k_b);
 
-        let mut temp: Vec<u8> = Vec::new();
+        let mut temp: Zeroizing<Vec<u8>> = Zeroizing::new(Vec::new());
         temp.extend_from_slice(&xv_bytes);
         temp.extend_from_slice(&self.rhs_za);
         temp.extend_from_slice(&self.za);
@@ -167,13 +224,13 @@ impl Exchange {
         let x1 = fp_from_mont(&ra_point_affine.x);
         let y1 = fp_from_mont(&ra_point_affine.y);
         let x1_a = u256_add(&pow, &u256_bits_and(&x1, &u256_sub(&pow, &SM2_ONE).0)).0;
-        let t_a = fn_add(
+        let t_a = Zeroizing::new(fn_add(
             &self.sk.d,
             &fn_mul(
                 &self.r.as_ref().unwrap(),
                 &x1_a,
             ),
-        );
+        ));
 
         let rb_point_affine = rb_point.to_affine_point();
         let x2 = fp_from_mont(&rb_point_affine.x);
@@ -183,25 +240,25 @@ impl Exchange {
             .rhs_pk
             .value()
             .point_add(&rb_point.scalar_mul(&x2_b));
-        let u_point = p.scalar_mul(&t_a);
+        let u_point = p.scalar_mul_ct(&t_a);
         if u_point.is_zero() {
             return Err(Sm2Error::ZeroPoint);
         }
 
         let u_affine_p = u_point.to_affine_point();
-        let xu_bytes = fp_from_mont(&u_affine_p.x).to_byte_be();
-        let yu_bytes = fp_from_mont(&u_affine_p.y).to_byte_be();
+        let xu_bytes = Zeroizing::new(fp_from_mont(&u_affine_p.x).to_byte_be());
+        let yu_bytes = Zeroizing::new(fp_from_mont(&u_affine_p.y).to_byte_be());
 
-        let mut prepend = Vec::new();
+        let mut prepend = Zeroizing::new(Vec::new());
         prepend.extend_from_slice(&xu_bytes);
         prepend.extend_from_slice(&yu_bytes);
         prepend.extend_from_slice(&self.za);
         prepend.extend_from_slice(&self.rhs_za);
 
-        let k_a = kdf(&prepend, self.klen);
+        let k_a = sm3_kdf(&prepend, self.klen);
         self.k = Some(k_a);
 
-        let mut temp: Vec<u8> = Vec::new();
+        let mut temp: Zeroizing<Vec<u8>> = Zeroizing::new(Vec::new());
         temp.extend_from_slice(&xu_bytes);
         temp.extend_from_slice(&self.za);
         temp.extend_from_slice(&self.rhs_za);
@@ -242,7 +299,7 @@ impl Exchange {
         let xv = fp_from_mont(&v_point_affine.x);
         let yv = fp_from_mont(&v_point_affine.y);
 
-        let mut temp: Vec<u8> = Vec::new();
+        let mut temp: Zeroizing<Vec<u8>> = Zeroizing::new(Vec::new());
         temp.extend_from_slice(&xv.to_byte_be());
         temp.extend_from_slice(&self.rhs_za);
         temp.extend_from_slice(&self.za);
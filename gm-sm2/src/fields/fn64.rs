@@ -1,5 +1,6 @@
 use crate::u256::{
-    SM2_ONE, U256, u256_add, u256_cmp, u256_mul, u256_sub, u512_add,
+    SM2_ONE, SM2_ZERO, U256, u256_add, u256_mul, u256_reduce_once, u256_select, u256_sub,
+    u512_add,
 };
 
 ///
@@ -43,22 +44,14 @@ pub const SM2_MOD_N_2E512: U256 = [
 
 pub fn fn_add(a: &U256, b: &U256) -> U256 {
     let (r, c) = u256_add(a, b);
-    if c {
-        // a + b - n = (a + b - 2^256) + (2^256 - n)
-        return u256_add(&r, &SM2_N_NEG).0;
-    }
-    if u256_cmp(&r, &SM2_N) >= 0 {
-        return u256_sub(&r, &SM2_N).0;
-    }
-    r
+    u256_reduce_once(&r, c, &SM2_N)
 }
 
 pub fn fn_sub(a: &U256, b: &U256) -> U256 {
-    let (mut r, c) = u256_sub(a, b);
-    if c {
-        r = u256_sub(&r, &SM2_N_NEG).0
-    }
-    r
+    // add n back on borrow, selected with a mask instead of a branch
+    let (r, c) = u256_sub(a, b);
+    let n = u256_select(&SM2_ZERO, &SM2_N, c as u64);
+    u256_add(&r, &n).0
 }
 
 pub fn fn_to_mont(a: &U256) -> U256 {
@@ -101,14 +94,9 @@ fn mont_mul(a: &U256, b: &U256) -> U256 {
     let (sum, c) = u512_add(&z, &t);
     z = sum;
 
-    // r = high(r)
+    // r = high(r), reduced without branching on the value
     r = [z[4], z[5], z[6], z[7]];
-    if c {
-        r = u256_add(&r, &SM2_N_NEG).0;
-    } else if u256_cmp(&r, &SM2_N) >= 0 {
-        r = u256_sub(&r, &SM2_N).0
-    }
-    r
+    u256_reduce_once(&r, c, &SM2_N)
 }
 
 pub fn fn_pow(a: &U256, e: &U256) -> U256 {
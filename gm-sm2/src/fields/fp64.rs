@@ -1,10 +1,10 @@
-use rand::RngCore;
+use rand::{CryptoRng, RngCore};
 
 use crate::error::{Sm2Error, Sm2Result};
 use crate::fields::FieldModOperation;
 use crate::u256::{
     SM2_ONE, SM2_ZERO, U256, u256_add, u256_cmp, u256_from_be_bytes, u256_mul,
-    u256_sub, u256_to_be_bytes, u512_add,
+    u256_reduce_once, u256_select, u256_sub, u256_to_be_bytes, u512_add,
 };
 
 // 0xfffffffeffffffffffffffffffffffffffffffff00000000ffffffffffffffff
@@ -89,8 +89,7 @@ pub const SM2_G_Y: U256 = [
 ];
 
 #[inline(always)]
-pub fn random_u256() -> U256 {
-    let mut rng = rand::thread_rng();
+pub fn random_u256_with_rng<R: CryptoRng + RngCore>(rng: &mut R) -> U256 {
     let mut buf: [u8; 32] = [0; 32];
     let mut ret;
     loop {
@@ -152,14 +151,9 @@ pub(crate) fn mont_mul(a: &U256, b: &U256) -> U256 {
     let (sum, c) = u512_add(&z, &t);
     z = sum;
 
-    // r = high(z)
+    // r = high(z), reduced without branching on the value
     r = [z[4], z[5], z[6], z[7]];
-    if c {
-        r = u256_add(&r, &SM2_MODP_MONT_ONE).0;
-    } else if u256_cmp(&r, &SM2_P) >= 0 {
-        r = u256_sub(&r, &SM2_P).0
-    }
-    r
+    u256_reduce_once(&r, c, &SM2_P)
 }
 
 pub fn fp_sqrt(a: &U256) -> Sm2Result<U256> {
@@ -200,25 +194,14 @@ impl FieldModOperation for U256 {
 
     fn fp_add(&self, rhs: &Self) -> Self {
         let (r, c) = u256_add(self, rhs);
-        if c {
-            let (diff, _borrow) = u256_add(&r, &SM2_MODP_MONT_ONE);
-            return diff;
-        }
-        if u256_cmp(&r, &SM2_P) >= 0 {
-            let (diff, _borrow) = u256_sub(&r, &SM2_P);
-            return diff;
-        }
-        r
+        u256_reduce_once(&r, c, &SM2_P)
     }
 
     fn fp_sub(&self, rhs: &Self) -> Self {
+        // add p back on borrow, selected with a mask instead of a branch
         let (raw_diff, borrow) = u256_sub(self, rhs);
-        if borrow {
-            let (diff, _borrow) = u256_sub(&raw_diff, &SM2_MODP_MONT_ONE);
-            diff
-        } else {
-            raw_diff
-        }
+        let p = u256_select(&SM2_ZERO, &SM2_P, borrow as u64);
+        u256_add(&raw_diff, &p).0
     }
 
     fn fp_mul(&self, rhs: &Self) -> Self {
@@ -1,16 +1,22 @@
-use hex::{FromHexError, ToHex};
-use num_bigint::BigUint;
+use std::fmt;
 
-use gm_sm3::sm3_hash;
+use hex::ToHex;
+use rand::{CryptoRng, RngCore};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
+use gm_sm3::{sm3_hash, sm3_kdf, Sm3};
+
+use crate::ciphertext::{C1Format, Sm2Ciphertext, Sm2CiphertextFormat};
 use crate::error::{Sm2Error, Sm2Result};
 use crate::fields::FieldModOperation;
 use crate::fields::fn64::{fn_add, fn_mul, fn_pow, fn_sub, SM2_N, SM2_N_MINUS_TWO};
-use crate::fields::fp64::{fp_from_mont, random_u256};
-use crate::p256_ecc::{g_mul, Point};
+use crate::fields::fp64::{fp_from_mont, random_u256_with_rng};
+use crate::p256_ecc::{g_mul, g_mul_ct, Point};
+use crate::rfc6979::NonceGenerator;
 use crate::u256::{SM2_ONE, U256, u256_add, u256_cmp, u256_from_be_bytes};
-use crate::util::{compute_za, DEFAULT_ID, kdf, xor_bytes};
+use crate::util::{compute_za, DEFAULT_ID, xor_bytes};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Sm2Model {
     C1C2C3,
     C1C3C2,
@@ -40,33 +46,66 @@ impl Sm2PublicKey {
     }
 
     /// Encrypt the given message and return ASN.1 data
+    ///
+    /// The DER structure is the GM/T 0009 `SM2Cipher`: the coordinates of C1,
+    /// then C3 and C2, whatever `compressed` and `model` are.
+    #[deprecated(note = "`compressed` and `model` are ignored, use `encrypt_der`")]
     pub fn encrypt_asn1(
         &self,
         msg: &[u8],
-        compressed: bool,
-        model: Sm2Model,
+        _compressed: bool,
+        _model: Sm2Model,
+    ) -> Sm2Result<Vec<u8>> {
+        self.encrypt_der(msg)
+    }
+
+    /// Encrypt the given message with randomness from `rng` and return ASN.1 data
+    #[deprecated(note = "`compressed` and `model` are ignored, use `encrypt_der_with_rng`")]
+    pub fn encrypt_asn1_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        msg: &[u8],
+        _compressed: bool,
+        _model: Sm2Model,
     ) -> Sm2Result<Vec<u8>> {
-        let cipher = self.encrypt(msg, compressed, model).unwrap();
-        let x = BigUint::from_bytes_be(&cipher[0..32]);
-        let y = BigUint::from_bytes_be(&cipher[32..64]);
-        let sm3 = &cipher[64..96];
-        let secret = &cipher[96..];
-        Ok(yasna::construct_der(|writer| {
-            writer.write_sequence(|writer| {
-                writer.next().write_biguint(&x);
-                writer.next().write_biguint(&y);
-                writer.next().write_bytes(&sm3);
-                writer.next().write_bytes(&secret);
-            });
-        }))
+        self.encrypt_der_with_rng(rng, msg)
+    }
+
+    /// Encrypt the given message and return the DER encoded GM/T 0009
+    /// `SM2Cipher`: the coordinates of C1, then C3 and C2.
+    pub fn encrypt_der(&self, msg: &[u8]) -> Sm2Result<Vec<u8>> {
+        self.encrypt_der_with_rng(&mut rand::thread_rng(), msg)
+    }
+
+    /// Encrypt the given message with randomness from `rng` and return the DER
+    /// encoded `SM2Cipher`.
+    pub fn encrypt_der_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        msg: &[u8],
+    ) -> Sm2Result<Vec<u8>> {
+        let format = Sm2CiphertextFormat::Raw(C1Format::Uncompressed, Sm2Model::C1C3C2);
+        let cipher = self.encrypt_with_rng(rng, msg, false, Sm2Model::C1C3C2)?;
+        Ok(Sm2Ciphertext::from_bytes(&cipher, format)?.to_bytes(Sm2CiphertextFormat::Asn1))
     }
 
     /// Encrypt the given message.
     pub fn encrypt(&self, msg: &[u8], compressed: bool, model: Sm2Model) -> Sm2Result<Vec<u8>> {
+        self.encrypt_with_rng(&mut rand::thread_rng(), msg, compressed, model)
+    }
+
+    /// Encrypt the given message, drawing the ephemeral key from `rng`.
+    pub fn encrypt_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        msg: &[u8],
+        compressed: bool,
+        model: Sm2Model,
+    ) -> Sm2Result<Vec<u8>> {
         loop {
             let klen = msg.len();
-            let k = random_u256();
-            let c1_p = g_mul(&k);
+            let k = Zeroizing::new(random_u256_with_rng(rng));
+            let c1_p = g_mul_ct(&k);
             let c1_p = c1_p.to_affine_point(); // 根据加密算法，z坐标会被丢弃，为保证解密还原回来的坐标在曲线上，则必须转换坐标系到 affine 坐标系
 
             let s_p = self.point.scalar_mul(&SM2_ONE);
@@ -74,16 +113,17 @@ impl Sm2PublicKey {
                 return Err(Sm2Error::ZeroPoint);
             }
 
-            let c2_p = self.point.scalar_mul(&k).to_affine_point();
-            let x2_bytes = fp_from_mont(&c2_p.x).to_byte_be();
-            let y2_bytes = fp_from_mont(&c2_p.y).to_byte_be();
-            let mut c2_append = vec![];
+            let c2_p = self.point.scalar_mul_ct(&k).to_affine_point();
+            let x2_bytes = Zeroizing::new(fp_from_mont(&c2_p.x).to_byte_be());
+            let y2_bytes = Zeroizing::new(fp_from_mont(&c2_p.y).to_byte_be());
+            let mut c2_append = Zeroizing::new(vec![]);
             c2_append.extend_from_slice(&x2_bytes);
             c2_append.extend_from_slice(&y2_bytes);
 
-            let t = kdf(&c2_append[..], klen);
-            let mut flag = true;
-            for elem in &t {
+            let t = Zeroizing::new(sm3_kdf(&c2_append[..], klen));
+            // an empty message has an empty key stream and nothing to mask
+            let mut flag = !t.is_empty();
+            for elem in t.iter() {
                 if elem != &0 {
                     flag = false;
                     break;
@@ -91,7 +131,7 @@ impl Sm2PublicKey {
             }
             if !flag {
                 let c2 = xor_bytes(msg, &t[..]);
-                let mut c3_append: Vec<u8> = vec![];
+                let mut c3_append: Zeroizing<Vec<u8>> = Zeroizing::new(vec![]);
                 c3_append.extend_from_slice(&x2_bytes);
                 c3_append.extend_from_slice(msg);
                 c3_append.extend_from_slice(&y2_bytes);
@@ -114,17 +154,36 @@ impl Sm2PublicKey {
         }
     }
 
-    pub fn verify(&self, id: Option<&'static str>, msg: &[u8], sig: &[u8]) -> Sm2Result<()> {
-        let id = id.unwrap_or_else(|| DEFAULT_ID);
-        let mut digest = compute_za(id, &self.point)?;
-        digest = sm3_hash(&[digest.to_vec(), msg.to_vec()].concat());
-        self.verify_raw(&digest[..], &self.point, sig)
+    pub fn verify(&self, id: Option<&[u8]>, msg: &[u8], sig: &[u8]) -> Sm2Result<()> {
+        let mut hasher = self.za_hasher(id)?;
+        hasher.update(msg);
+        self.verify_raw(&hasher.finalize(), &self.point, sig)
+    }
+
+    /// Verify the signature over a precomputed digest `e = SM3(ZA || M)`.
+    pub fn verify_digest(&self, e: &[u8], sig: &[u8]) -> Sm2Result<()> {
+        self.verify_raw(e, &self.point, sig)
+    }
+
+    /// Return an SM3 hasher already fed with `ZA = compute_za(id, pk)`.
+    ///
+    /// Update it with the message and pass the result to
+    /// [`Sm2PrivateKey::sign_digest`] or [`Sm2PublicKey::verify_digest`], so
+    /// large messages can be signed without holding them in memory.
+    pub fn za_hasher(&self, id: Option<&[u8]>) -> Sm2Result<Sm3> {
+        let id = id.unwrap_or(DEFAULT_ID);
+        let mut hasher = Sm3::new();
+        hasher.update(&compute_za(id, &self.point)?);
+        Ok(hasher)
     }
 
     fn verify_raw(&self, digest: &[u8], pk: &Point, sig: &[u8]) -> Sm2Result<()> {
         if digest.len() != 32 {
             return Err(Sm2Error::InvalidDigestLen);
         }
+        if sig.len() != 64 {
+            return Err(Sm2Error::InvalidSignature);
+        }
         let n = &SM2_N;
         let r = &u256_from_be_bytes(&sig[..32]);
         let s = &u256_from_be_bytes(&sig[32..]);
@@ -156,14 +215,9 @@ impl Sm2PublicKey {
         bytes.encode_hex::<String>()
     }
 
-    pub fn from_hex_string(hex_str: &str) -> Result<Self, FromHexError> {
-        let bytes = hex::decode(hex_str);
-        match bytes {
-            Ok(b) => Ok(Self {
-                point: Point::from_byte(b.as_slice()).unwrap(),
-            }),
-            Err(e) => Err(e),
-        }
+    pub fn from_hex_string(hex_str: &str) -> Result<Self, String> {
+        let bytes = hex::decode(hex_str).map_err(|e| e.to_string())?;
+        Self::new(&bytes).map_err(|e| e.to_string())
     }
 
     pub fn value(&self) -> &Point {
@@ -171,12 +225,34 @@ impl Sm2PublicKey {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Sm2PrivateKey {
     pub d: U256,
     pub public_key: Sm2PublicKey,
 }
 
+impl fmt::Debug for Sm2PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sm2PrivateKey")
+            .field("public_key", &self.public_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Zeroize for Sm2PrivateKey {
+    fn zeroize(&mut self) {
+        self.d.zeroize();
+    }
+}
+
+impl Drop for Sm2PrivateKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Sm2PrivateKey {}
+
 impl Eq for Sm2PrivateKey {}
 
 impl PartialEq<Self> for Sm2PrivateKey {
@@ -194,9 +270,9 @@ impl AsRef<Sm2PublicKey> for Sm2PrivateKey {
 
 impl Sm2PrivateKey {
     pub fn new(sk: &[u8]) -> Sm2Result<Self> {
-        let d = u256_from_be_bytes(sk);
+        let d = Zeroizing::new(u256_from_be_bytes(sk));
         let public_key = public_from_private(&d)?;
-        let private_key = Self { d, public_key };
+        let private_key = Self { d: *d, public_key };
         Ok(private_key)
     }
 
@@ -206,14 +282,132 @@ impl Sm2PrivateKey {
     }
 
     /// Sign the given digest.
-    pub fn sign(&self, id: Option<&'static str>, msg: &[u8]) -> Sm2Result<Vec<u8>> {
-        let id = id.unwrap_or_else(|| DEFAULT_ID);
-        let mut digest = compute_za(id, &self.public_key.point)?;
-        digest = sm3_hash(&[digest.to_vec(), msg.to_vec()].concat());
-        self.sign_raw(&digest[..], &self.d)
+    pub fn sign(&self, id: Option<&[u8]>, msg: &[u8]) -> Sm2Result<Vec<u8>> {
+        self.sign_with_rng(&mut rand::thread_rng(), id, msg)
+    }
+
+    /// Sign the given message, drawing the nonce `k` from `rng`.
+    pub fn sign_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        id: Option<&[u8]>,
+        msg: &[u8],
+    ) -> Sm2Result<Vec<u8>> {
+        let mut hasher = self.public_key.za_hasher(id)?;
+        hasher.update(msg);
+        self.sign_digest_with_rng(rng, &hasher.finalize())
+    }
+
+    /// Sign a precomputed digest `e = SM3(ZA || M)`, see [`Sm2PublicKey::za_hasher`].
+    pub fn sign_digest(&self, e: &[u8]) -> Sm2Result<Vec<u8>> {
+        self.sign_digest_with_rng(&mut rand::thread_rng(), e)
+    }
+
+    /// Sign a precomputed digest, drawing the nonce `k` from `rng`.
+    pub fn sign_digest_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        e: &[u8],
+    ) -> Sm2Result<Vec<u8>> {
+        self.sign_raw(e, &self.d, || random_u256_with_rng(rng))
+    }
+
+    /// Sign the given message and also return the recovery id needed by
+    /// [`crate::recovery::recover_public_key`].
+    ///
+    /// The signature itself is the same `r || s` as [`Sm2PrivateKey::sign`].
+    pub fn sign_recoverable(&self, id: Option<&[u8]>, msg: &[u8]) -> Sm2Result<(Vec<u8>, u8)> {
+        self.sign_recoverable_with_rng(&mut rand::thread_rng(), id, msg)
+    }
+
+    /// Like [`Sm2PrivateKey::sign_recoverable`], drawing the nonce `k` from `rng`.
+    pub fn sign_recoverable_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        id: Option<&[u8]>,
+        msg: &[u8],
+    ) -> Sm2Result<(Vec<u8>, u8)> {
+        let mut hasher = self.public_key.za_hasher(id)?;
+        hasher.update(msg);
+        self.sign_digest_recoverable_with_rng(rng, &hasher.finalize())
+    }
+
+    /// Sign a precomputed digest and also return the recovery id.
+    pub fn sign_digest_recoverable(&self, e: &[u8]) -> Sm2Result<(Vec<u8>, u8)> {
+        self.sign_digest_recoverable_with_rng(&mut rand::thread_rng(), e)
+    }
+
+    /// Like [`Sm2PrivateKey::sign_digest_recoverable`], drawing the nonce `k` from `rng`.
+    pub fn sign_digest_recoverable_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        e: &[u8],
+    ) -> Sm2Result<(Vec<u8>, u8)> {
+        self.sign_raw_recoverable(e, &self.d, || random_u256_with_rng(rng))
+    }
+
+    /// Sign the given message with a deterministic nonce derived from the
+    /// private key and the digest (RFC 6979 with HMAC-SM3). The same message
+    /// always yields the same signature and no RNG is involved.
+    pub fn sign_deterministic(&self, id: Option<&[u8]>, msg: &[u8]) -> Sm2Result<Vec<u8>> {
+        self.sign_hedged_raw(id, msg, &[])
+    }
+
+    /// Sign the given message with a hedged nonce: the RFC 6979 derivation
+    /// additionally mixes in 32 bytes of fresh randomness.
+    pub fn sign_hedged(&self, id: Option<&[u8]>, msg: &[u8]) -> Sm2Result<Vec<u8>> {
+        self.sign_hedged_with_rng(&mut rand::thread_rng(), id, msg)
+    }
+
+    /// Like [`Sm2PrivateKey::sign_hedged`], with the extra randomness drawn from `rng`.
+    pub fn sign_hedged_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        id: Option<&[u8]>,
+        msg: &[u8],
+    ) -> Sm2Result<Vec<u8>> {
+        let mut extra = [0u8; 32];
+        rng.fill_bytes(&mut extra);
+        self.sign_hedged_raw(id, msg, &extra)
+    }
+
+    fn sign_hedged_raw(
+        &self,
+        id: Option<&[u8]>,
+        msg: &[u8],
+        extra: &[u8],
+    ) -> Sm2Result<Vec<u8>> {
+        let mut hasher = self.public_key.za_hasher(id)?;
+        hasher.update(msg);
+        self.sign_digest_hedged_raw(&hasher.finalize(), extra)
     }
 
-    fn sign_raw(&self, digest: &[u8], sk: &U256) -> Sm2Result<Vec<u8>> {
+    pub(crate) fn sign_digest_hedged_raw(&self, e: &[u8], extra: &[u8]) -> Sm2Result<Vec<u8>> {
+        if e.len() != 32 {
+            return Err(Sm2Error::InvalidDigestLen);
+        }
+        let mut nonces = NonceGenerator::new(&self.d, e, extra);
+        self.sign_raw(e, &self.d, || nonces.next_k())
+    }
+
+    fn sign_raw<F: FnMut() -> U256>(
+        &self,
+        digest: &[u8],
+        sk: &U256,
+        next_k: F,
+    ) -> Sm2Result<Vec<u8>> {
+        self.sign_raw_recoverable(digest, sk, next_k)
+            .map(|(sig, _)| sig)
+    }
+
+    /// Like `sign_raw`, also returning the recovery id of `R = [k]G`: bit 0 is
+    /// the parity of `y1`, bit 1 is set when `x1 >= n`.
+    pub(crate) fn sign_raw_recoverable<F: FnMut() -> U256>(
+        &self,
+        digest: &[u8],
+        sk: &U256,
+        mut next_k: F,
+    ) -> Sm2Result<(Vec<u8>, u8)> {
         if digest.len() != 32 {
             return Err(Sm2Error::InvalidDigestLen);
         }
@@ -221,15 +415,15 @@ impl Sm2PrivateKey {
         let n = &SM2_N;
         let s1 = fn_pow(&u256_add(&SM2_ONE, &sk).0, &SM2_N_MINUS_TWO);
         loop {
-            let k = random_u256();
-            let p_x = g_mul(&k).to_affine_point();
+            let k = Zeroizing::new(next_k());
+            let p_x = g_mul_ct(&k).to_affine_point();
             let x1 = u256_from_be_bytes(&fp_from_mont(&p_x.x).to_byte_be());
             let r = fn_add(&e, &x1);
             if r.is_zero() || u256_add(&r, &k).0 == *n {
                 continue;
             }
             let s2_1 = fn_mul(&r, &sk);
-            let s2 = fn_sub(&k, &s2_1);
+            let s2 = Zeroizing::new(fn_sub(&k, &s2_1));
             let s = fn_mul(&s1, &s2);
             if s.is_zero() {
                 continue;
@@ -237,35 +431,31 @@ impl Sm2PrivateKey {
             let mut sig: Vec<u8> = vec![];
             sig.extend_from_slice(&r.to_byte_be());
             sig.extend_from_slice(&s.to_byte_be());
-            return Ok(sig);
+            let y_odd = fp_from_mont(&p_x.y)[0] & 1;
+            let x_overflow = (u256_cmp(&x1, n) >= 0) as u8;
+            return Ok((sig, y_odd as u8 | x_overflow << 1));
         }
     }
 
     /// Decrypt the given ASN.1 message.
+    ///
+    /// `compressed` and `model` are ignored, see [`Sm2PrivateKey::decrypt_der`].
+    #[deprecated(note = "`compressed` and `model` are ignored, use `decrypt_der`")]
     pub fn decrypt_asn1(
         &self,
         ciphertext: &[u8],
-        compressed: bool,
-        model: Sm2Model,
+        _compressed: bool,
+        _model: Sm2Model,
     ) -> Sm2Result<Vec<u8>> {
-        let (x, y, sm3, secret) = yasna::parse_der(ciphertext, |reader| {
-            reader.read_sequence(|reader| {
-                let x = reader.next().read_biguint()?;
-                let y = reader.next().read_biguint()?;
-                let sm3 = reader.next().read_bytes()?;
-                let secret = reader.next().read_bytes()?;
-                return Ok((x, y, sm3, secret));
-            })
-        })
-        .unwrap();
-        let x = BigUint::to_bytes_be(&x);
-        let y = BigUint::to_bytes_be(&y);
-        let mut cipher: Vec<u8> = vec![];
-        cipher.extend_from_slice(&x);
-        cipher.extend_from_slice(&y);
-        cipher.extend_from_slice(&sm3);
-        cipher.extend_from_slice(&secret);
-        self.decrypt(&cipher, compressed, model)
+        self.decrypt_der(ciphertext)
+    }
+
+    /// Decrypt a DER encoded GM/T 0009 `SM2Cipher`.
+    ///
+    /// Anything but a DER `SM2Cipher` without trailing data is rejected.
+    pub fn decrypt_der(&self, ciphertext: &[u8]) -> Sm2Result<Vec<u8>> {
+        let cipher = Sm2Ciphertext::from_bytes(ciphertext, Sm2CiphertextFormat::Asn1)?;
+        self.decrypt_ciphertext(&cipher)
     }
 
     /// Decrypt the given message.
@@ -275,66 +465,31 @@ impl Sm2PrivateKey {
         compressed: bool,
         model: Sm2Model,
     ) -> Sm2Result<Vec<u8>> {
-        let c1_end_index = match compressed {
-            true => 33,
-            false => 65,
-        };
-        let c1_bytes = &ciphertext[0..c1_end_index];
-        let len = ciphertext.len();
-        let c2_bytes = match model {
-            Sm2Model::C1C2C3 => &ciphertext[c1_end_index..(len - 32)],
-            Sm2Model::C1C3C2 => &ciphertext[(c1_end_index + 32)..],
-        };
-        let c3_bytes = match model {
-            Sm2Model::C1C2C3 => &ciphertext[(len - 32)..],
-            Sm2Model::C1C3C2 => &ciphertext[c1_end_index..c1_end_index + 32],
-        };
-
-        let kelen = c2_bytes.len();
-        let c1_point = Point::from_byte(c1_bytes)?;
-        if !c1_point.to_affine_point().is_valid_affine_point() {
-            return Err(Sm2Error::CheckPointErr);
-        }
+        let (c1_point, c2_bytes, c3_bytes) = split_ciphertext(ciphertext, compressed, model)?;
+        let c2_point = c1_point.scalar_mul_ct(&self.d).to_affine_point();
+        decrypt_with_point(&c2_point, c2_bytes, c3_bytes)
+    }
 
-        let s_point = c1_point.scalar_mul(&SM2_ONE);
-        if s_point.is_zero() {
-            return Err(Sm2Error::ZeroPoint);
-        }
+    /// Decrypt an already parsed ciphertext.
+    pub fn decrypt_ciphertext(&self, ciphertext: &Sm2Ciphertext) -> Sm2Result<Vec<u8>> {
+        let c2_point = ciphertext.c1().scalar_mul_ct(&self.d).to_affine_point();
+        decrypt_with_point(&c2_point, ciphertext.c2(), ciphertext.c3())
+    }
 
-        let c2_point = c1_point.scalar_mul(&self.d).to_affine_point();
-        let x2_bytes = fp_from_mont(&c2_point.x).to_byte_be();
-        let y2_bytes = fp_from_mont(&c2_point.y).to_byte_be();
-        let mut prepend: Vec<u8> = vec![];
-        prepend.extend_from_slice(&x2_bytes);
-        prepend.extend_from_slice(&y2_bytes);
-        let t = kdf(&prepend, kelen);
-        let mut flag = true;
-        for elem in &t {
-            if elem != &0 {
-                flag = false;
+    /// Decrypt a ciphertext in any layout known to [`Sm2Ciphertext`].
+    ///
+    /// Each candidate from [`Sm2Ciphertext::candidates`] is tried in turn and
+    /// the first one whose `C3` checks is returned, so a ciphertext in an
+    /// ambiguous layout costs up to one scalar multiplication per candidate.
+    pub fn decrypt_any(&self, ciphertext: &[u8]) -> Sm2Result<Vec<u8>> {
+        let mut result = Err(Sm2Error::InvalidCiphertext);
+        for (candidate, _) in Sm2Ciphertext::candidates(ciphertext) {
+            result = self.decrypt_ciphertext(&candidate);
+            if result.is_ok() {
                 break;
             }
         }
-        if flag {
-            return Err(Sm2Error::ZeroData);
-        }
-
-        let m = xor_bytes(c2_bytes, &t);
-        let mut mb = m;
-        if mb.len() < kelen {
-            for i in 0..kelen - mb.len() {
-                mb.insert(i, 0);
-            }
-        }
-        let mut prepend: Vec<u8> = vec![];
-        prepend.extend_from_slice(&x2_bytes);
-        prepend.extend_from_slice(&mb);
-        prepend.extend_from_slice(&y2_bytes);
-        let u = sm3_hash(&prepend);
-        if u != c3_bytes {
-            return Err(Sm2Error::HashNotEqual);
-        }
-        Ok(mb)
+        result
     }
 
     pub fn to_hex_string(&self) -> String {
@@ -361,16 +516,118 @@ impl Sm2PrivateKey {
     }
 }
 
+/// Split `C1 || C2 || C3` (or `C1 || C3 || C2`) and check that `C1` is a valid
+/// point.
+pub(crate) fn split_ciphertext(
+    ciphertext: &[u8],
+    compressed: bool,
+    model: Sm2Model,
+) -> Sm2Result<(Point, &[u8], &[u8])> {
+    let c1_end_index = match compressed {
+        true => 33,
+        false => 65,
+    };
+    // C2 is empty for an empty message
+    if ciphertext.len() < c1_end_index + 32 {
+        return Err(Sm2Error::InvalidCiphertext);
+    }
+    let c1_point = read_c1(&ciphertext[0..c1_end_index], compressed)?;
+    let len = ciphertext.len();
+    let c2_bytes = match model {
+        Sm2Model::C1C2C3 => &ciphertext[c1_end_index..(len - 32)],
+        Sm2Model::C1C3C2 => &ciphertext[(c1_end_index + 32)..],
+    };
+    let c3_bytes = match model {
+        Sm2Model::C1C2C3 => &ciphertext[(len - 32)..],
+        Sm2Model::C1C3C2 => &ciphertext[c1_end_index..c1_end_index + 32],
+    };
+
+    Ok((c1_point, c2_bytes, c3_bytes))
+}
+
+/// Parse `C1` and check that it is a valid point with the expected encoding.
+pub(crate) fn read_c1(c1_bytes: &[u8], compressed: bool) -> Sm2Result<Point> {
+    let prefix_ok = match compressed {
+        true => c1_bytes[0] == 0x02 || c1_bytes[0] == 0x03,
+        false => c1_bytes[0] == 0x04,
+    };
+    if !prefix_ok {
+        return Err(Sm2Error::InvalidCiphertext);
+    }
+
+    let c1_point = Point::from_byte(c1_bytes)?;
+    if !c1_point.to_affine_point().is_valid_affine_point() {
+        return Err(Sm2Error::CheckPointErr);
+    }
+
+    let s_point = c1_point.scalar_mul(&SM2_ONE);
+    if s_point.is_zero() {
+        return Err(Sm2Error::ZeroPoint);
+    }
+    Ok(c1_point)
+}
+
+/// Finish decryption with `[d]C1 = (x2, y2)` in affine form: derive the key
+/// stream, recover `M` and check `C3`.
+pub(crate) fn decrypt_with_point(
+    c2_point: &Point,
+    c2_bytes: &[u8],
+    c3_bytes: &[u8],
+) -> Sm2Result<Vec<u8>> {
+    let kelen = c2_bytes.len();
+    let x2_bytes = Zeroizing::new(fp_from_mont(&c2_point.x).to_byte_be());
+    let y2_bytes = Zeroizing::new(fp_from_mont(&c2_point.y).to_byte_be());
+    let mut prepend: Zeroizing<Vec<u8>> = Zeroizing::new(vec![]);
+    prepend.extend_from_slice(&x2_bytes);
+    prepend.extend_from_slice(&y2_bytes);
+    let t = Zeroizing::new(sm3_kdf(&prepend, kelen));
+    let mut flag = !t.is_empty();
+    for elem in t.iter() {
+        if elem != &0 {
+            flag = false;
+            break;
+        }
+    }
+    if flag {
+        return Err(Sm2Error::ZeroData);
+    }
+
+    let m = xor_bytes(c2_bytes, &t);
+    // the plaintext is wiped again if the C3 check fails
+    let mut mb = Zeroizing::new(m);
+    if mb.len() < kelen {
+        for i in 0..kelen - mb.len() {
+            mb.insert(i, 0);
+        }
+    }
+    let mut prepend: Zeroizing<Vec<u8>> = Zeroizing::new(vec![]);
+    prepend.extend_from_slice(&x2_bytes);
+    prepend.extend_from_slice(&mb);
+    prepend.extend_from_slice(&y2_bytes);
+    let u = sm3_hash(&prepend);
+    if u != c3_bytes {
+        return Err(Sm2Error::HashNotEqual);
+    }
+    Ok(std::mem::take(&mut *mb))
+}
+
 /// generate key pair
 pub fn gen_keypair() -> Sm2Result<(Sm2PublicKey, Sm2PrivateKey)> {
-    let d = random_u256();
+    gen_keypair_with_rng(&mut rand::thread_rng())
+}
+
+/// generate key pair with the private key drawn from `rng`
+pub fn gen_keypair_with_rng<R: CryptoRng + RngCore>(
+    rng: &mut R,
+) -> Sm2Result<(Sm2PublicKey, Sm2PrivateKey)> {
+    let d = Zeroizing::new(random_u256_with_rng(rng));
     let pk = public_from_private(&d)?;
-    let sk = Sm2PrivateKey { d, public_key: pk };
+    let sk = Sm2PrivateKey { d: *d, public_key: pk };
     Ok((pk, sk))
 }
 
 fn public_from_private(sk: &U256) -> Sm2Result<Sm2PublicKey> {
-    let p = g_mul(&sk);
+    let p = g_mul_ct(sk);
     if p.is_valid() {
         Ok(Sm2PublicKey { point: p })
     } else {
@@ -3,14 +3,29 @@
 use pkcs8::ObjectIdentifier;
 use pkcs8::spki::AlgorithmIdentifier;
 
+pub use crate::batch::batch_verify;
+pub use crate::recovery::recover_public_key;
+
+pub mod batch;
+pub mod ciphertext;
+pub mod collab;
+#[cfg(feature = "cms")]
+pub mod envelope;
 pub mod error;
 pub mod exchange;
 pub mod key;
 pub mod p256_ecc;
 pub mod util;
 pub mod pkcs;
+pub mod recovery;
+pub mod signature;
+#[cfg(feature = "cms")]
+pub mod signed;
+pub mod stream;
+pub mod threshold;
 pub mod u256;
 pub(crate) mod fields;
+pub(crate) mod rfc6979;
 pub(crate) mod sm2p256_table;
 
 /// Fp 的加法，减法，乘法并不是简单的四则运算。其运算结果的值必须在Fp的有限域中，这样保证椭圆曲线变成离散的点
@@ -63,6 +78,9 @@ const ALGORITHM_IDENTIFIER: AlgorithmIdentifier<ObjectIdentifier> = AlgorithmIde
 pub const OID_SM2_CMS_1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.156.10197.1.301.1");
 pub const OID_SM2_CMS_3: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.156.10197.1.301.3");
 
+/// SM2 signature with SM3 (sm2sign-with-sm3), refer to GM/T 0006
+pub const OID_SM2_SM3: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.156.10197.1.501");
+
 /// oid refer to GM/T 0010  pkcs#7
 pub const OID_SM2_CMS_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.156.10197.6.1.4.2.1");
 pub const OID_SM2_CMS_SIGNED: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.156.10197.6.1.4.2.2");
@@ -73,8 +91,13 @@ pub const OID_SM2_CMS_KEY_AGREEMENT_INFO: ObjectIdentifier = ObjectIdentifier::n
 
 #[cfg(test)]
 mod test_sm2 {
+    use gm_sm3::Sm3Drbg;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::error::Sm2Error;
     use crate::exchange;
-    use crate::key::{gen_keypair, Sm2Model, Sm2PrivateKey, Sm2PublicKey};
+    use crate::key::{gen_keypair, gen_keypair_with_rng, Sm2Model, Sm2PrivateKey, Sm2PublicKey};
 
     #[test]
     fn test_encrypt_decrypt_with_gen_key() {
@@ -88,6 +111,18 @@ mod test_sm2 {
         assert_eq!(msg, plain)
     }
 
+    #[test]
+    fn test_encrypt_empty_msg() {
+        // an empty message is encrypted to C1 || C3 with an empty C2
+        let (pk, sk) = gen_keypair().unwrap();
+        let encrypted = pk.encrypt(&[], false, Sm2Model::C1C3C2).unwrap();
+        assert_eq!(65 + 32, encrypted.len());
+        assert!(sk.decrypt(&encrypted, false, Sm2Model::C1C3C2).unwrap().is_empty());
+        let encrypted = pk.encrypt(&[], true, Sm2Model::C1C2C3).unwrap();
+        assert_eq!(33 + 32, encrypted.len());
+        assert!(sk.decrypt(&encrypted, true, Sm2Model::C1C2C3).unwrap().is_empty());
+    }
+
     #[test]
     fn test_encrypt_decrypt_with_special_key() {
         let public_key = "048626c62a8582c639cb3c87b59118713a519988c5f6497f91dd672abbdaaed0420ea7bc2cd03a7c938adc42b450549d312bec823b74cf22cf57c63cebd011c595";
@@ -146,10 +181,145 @@ mod test_sm2 {
         pk.verify(None, msg, &signature).unwrap();
     }
 
+    #[test]
+    fn test_sign_verify_with_byte_id() {
+        let msg = b"hello";
+        let (pk, sk) = gen_keypair().unwrap();
+
+        let id: Vec<u8> = String::from("alice@example.com").into_bytes();
+        let signature = sk.sign(Some(&id), msg).unwrap();
+        pk.verify(Some(&id), msg, &signature).unwrap();
+        assert!(pk.verify(None, msg, &signature).is_err());
+
+        let binary_id = [0x00, 0xff, 0x80, 0x01];
+        let signature = sk.sign(Some(&binary_id), msg).unwrap();
+        pk.verify(Some(&binary_id), msg, &signature).unwrap();
+
+        let signature = sk.sign(None, msg).unwrap();
+        pk.verify(Some(b"1234567812345678"), msg, &signature).unwrap();
+    }
+
+    #[test]
+    fn test_sign_verify_digest() {
+        let (pk, sk) = gen_keypair().unwrap();
+        let chunks: [&[u8]; 3] = [b"a large message ", b"signed in ", b"pieces"];
+
+        let mut hasher = pk.za_hasher(Some(b"alice")).unwrap();
+        for chunk in chunks.iter() {
+            hasher.update(chunk);
+        }
+        let e = hasher.finalize();
+        let signature = sk.sign_digest(&e).unwrap();
+        pk.verify_digest(&e, &signature).unwrap();
+        pk.verify(Some(b"alice"), &chunks.concat(), &signature).unwrap();
+
+        let signature = sk.sign(Some(b"alice"), &chunks.concat()).unwrap();
+        pk.verify_digest(&e, &signature).unwrap();
+
+        assert_eq!(sk.sign_digest(&e[..31]), Err(Sm2Error::InvalidDigestLen));
+        assert_eq!(pk.verify_digest(&e[..31], &signature), Err(Sm2Error::InvalidDigestLen));
+    }
+
+    #[test]
+    fn test_id_too_long() {
+        let msg = b"hello";
+        let (pk, sk) = gen_keypair().unwrap();
+        let id = vec![0x61; 8191];
+        let signature = sk.sign(Some(&id), msg).unwrap();
+        pk.verify(Some(&id), msg, &signature).unwrap();
+
+        let id = vec![0x61; 8192];
+        assert_eq!(sk.sign(Some(&id), msg), Err(Sm2Error::IdTooLong));
+        assert_eq!(pk.verify(Some(&id), msg, &signature), Err(Sm2Error::IdTooLong));
+    }
+
+    #[test]
+    fn test_sign_deterministic() {
+        let msg = b"hello world";
+        let public_key = "048626c62a8582c639cb3c87b59118713a519988c5f6497f91dd672abbdaaed0420ea7bc2cd03a7c938adc42b450549d312bec823b74cf22cf57c63cebd011c595";
+        let private_key = "eb20009ffbffc90aeeb288ca7d782c722332d1d16a206cafec7dd6c64e6fc525";
+        let pk = Sm2PublicKey::from_hex_string(public_key).unwrap();
+        let sk = Sm2PrivateKey::from_hex_string(private_key).unwrap();
+        let signature = sk.sign_deterministic(None, msg).unwrap();
+        assert_eq!(
+            "c59da095c36aa05ef6867074945249a09ffe8389d74202f5c2d42d5ed872676d\
+             ba9d2cb622da52bf9737af0bbf43519f52e43790f0fbcadfe33f8a961bb594d3",
+            hex::encode(&signature)
+        );
+        assert_eq!(signature, sk.sign_deterministic(None, msg).unwrap());
+        pk.verify(None, msg, &signature).unwrap();
+
+        let other = sk.sign_deterministic(None, b"hello world!").unwrap();
+        assert_ne!(signature[..32], other[..32]);
+        pk.verify(None, b"hello world!", &other).unwrap();
+    }
+
+    #[test]
+    fn test_sign_hedged() {
+        let msg = b"hello";
+        let (pk, sk) = gen_keypair().unwrap();
+        let sig1 = sk.sign_hedged(None, msg).unwrap();
+        let sig2 = sk.sign_hedged(None, msg).unwrap();
+        assert_ne!(sig1, sig2);
+        pk.verify(None, msg, &sig1).unwrap();
+        pk.verify(None, msg, &sig2).unwrap();
+
+        let mut rng1 = StdRng::seed_from_u64(1);
+        let mut rng2 = StdRng::seed_from_u64(1);
+        assert_eq!(
+            sk.sign_hedged_with_rng(&mut rng1, None, msg).unwrap(),
+            sk.sign_hedged_with_rng(&mut rng2, None, msg).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_seeded_rng_is_reproducible() {
+        let msg = b"hello";
+        let mut rng1 = StdRng::seed_from_u64(42);
+        let mut rng2 = StdRng::seed_from_u64(42);
+        let (pk1, sk1) = gen_keypair_with_rng(&mut rng1).unwrap();
+        let (pk2, sk2) = gen_keypair_with_rng(&mut rng2).unwrap();
+        assert_eq!(sk1, sk2);
+
+        let sig1 = sk1.sign_with_rng(&mut rng1, None, msg).unwrap();
+        let sig2 = sk2.sign_with_rng(&mut rng2, None, msg).unwrap();
+        assert_eq!(sig1, sig2);
+        pk1.verify(None, msg, &sig1).unwrap();
+
+        let c1 = pk1.encrypt_with_rng(&mut rng1, msg, false, Sm2Model::C1C3C2).unwrap();
+        let c2 = pk2.encrypt_with_rng(&mut rng2, msg, false, Sm2Model::C1C3C2).unwrap();
+        assert_eq!(c1, c2);
+        assert_eq!(msg.to_vec(), sk1.decrypt(&c1, false, Sm2Model::C1C3C2).unwrap());
+    }
+
+    #[test]
+    fn test_sign_encrypt_with_drbg() {
+        let msg = b"hello";
+        let mut drbg = Sm3Drbg::new().unwrap();
+        let (pk, sk) = gen_keypair_with_rng(&mut drbg).unwrap();
+        let signature = sk.sign_with_rng(&mut drbg, None, msg).unwrap();
+        pk.verify(None, msg, &signature).unwrap();
+        let cipher = pk.encrypt_with_rng(&mut drbg, msg, true, Sm2Model::C1C2C3).unwrap();
+        assert_eq!(msg.to_vec(), sk.decrypt(&cipher, true, Sm2Model::C1C2C3).unwrap());
+    }
+
+    #[test]
+    fn test_key_exchange_with_rng() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let (mut alice, mut bob) =
+            exchange::build_ex_pair_with_rng(&mut rng, 16, b"alice123@qq.com", b"bob456@qq.com")
+                .unwrap();
+        let ra_point = alice.exchange_1_with_rng(&mut rng).unwrap();
+        let (rb_point, sb) = bob.exchange_2_with_rng(&mut rng, &ra_point).unwrap();
+        let sa = alice.exchange_3(&rb_point, sb).unwrap();
+        assert!(bob.exchange_4(sa, &ra_point).unwrap());
+        assert_eq!(alice.k, bob.k);
+    }
+
     #[test]
     fn test_key_exchange() {
-        let id_a = "alice123@qq.com";
-        let id_b = "bob456@qq.com";
+        let id_a = b"alice123@qq.com";
+        let id_b = b"bob456@qq.com";
 
         let (mut alice, mut bob) = exchange::build_ex_pair(8, id_a, id_b).unwrap();
 
@@ -161,8 +331,31 @@ mod test_sm2 {
         assert_eq!(alice.k, bob.k);
     }
 
+    #[test]
+    fn test_zeroize_and_redacted_debug() {
+        use zeroize::Zeroize;
+
+        let private_key = "eb20009ffbffc90aeeb288ca7d782c722332d1d16a206cafec7dd6c64e6fc525";
+        let mut sk = Sm2PrivateKey::from_hex_string(private_key).unwrap();
+        let debug = format!("{:?}", sk);
+        assert!(debug.starts_with("Sm2PrivateKey"));
+        assert!(!debug.contains("d:"));
+        sk.zeroize();
+        assert_eq!(sk.d, [0; 4]);
+
+        let (mut alice, mut bob) = exchange::build_ex_pair(16, b"alice", b"bob").unwrap();
+        let ra_point = alice.exchange_1().unwrap();
+        let (rb_point, sb) = bob.exchange_2(&ra_point).unwrap();
+        alice.exchange_3(&rb_point, sb).unwrap();
+        let debug = format!("{:?}", alice);
+        assert!(!debug.contains("sk:") && !debug.contains(" k:") && !debug.contains(" r:"));
+        alice.zeroize();
+        assert!(alice.k.is_none());
+    }
+
 
     #[test]
+    #[allow(deprecated)]
     fn test_encrypt_decrypt_asn1_with_special_key() {
         let public_key = "048626c62a8582c639cb3c87b59118713a519988c5f6497f91dd672abbdaaed0420ea7bc2cd03a7c938adc42b450549d312bec823b74cf22cf57c63cebd011c595";
         let private_key = "eb20009ffbffc90aeeb288ca7d782c722332d1d16a206cafec7dd6c64e6fc525";
@@ -173,5 +366,160 @@ mod test_sm2 {
         let encrypt = pk.encrypt_asn1(msg, false, Sm2Model::C1C3C2).unwrap();
         let plain = sk.decrypt_asn1(&encrypt, false, Sm2Model::C1C3C2).unwrap();
         assert_eq!(msg, plain);
+        // the layout arguments of the deprecated functions are ignored
+        assert_eq!(msg, sk.decrypt_der(&encrypt).unwrap());
+        let encrypt = pk.encrypt_der(msg).unwrap();
+        assert_eq!(msg, sk.decrypt_asn1(&encrypt, true, Sm2Model::C1C2C3).unwrap());
+    }
+
+    #[test]
+    fn test_public_key_from_invalid_hex() {
+        let x = "8626c62a8582c639cb3c87b59118713a519988c5f6497f91dd672abbdaaed042";
+        let y = "0ea7bc2cd03a7c938adc42b450549d312bec823b74cf22cf57c63cebd011c595";
+        assert!(Sm2PublicKey::from_hex_string(&format!("04{}{}", x, y)).is_ok());
+        for bad in [
+            String::new(),
+            "zz".to_string(),
+            format!("05{}{}", x, y),
+            format!("04{}{}", "ff".repeat(32), y),
+            format!("04{}{}", x, x),
+        ] {
+            assert!(Sm2PublicKey::from_hex_string(&bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_malformed() {
+        let (pk, sk) = gen_keypair().unwrap();
+        let msg = b"attack at dawn";
+        for compressed in [false, true] {
+            for model in [Sm2Model::C1C2C3, Sm2Model::C1C3C2] {
+                let cipher = pk.encrypt(msg, compressed, model).unwrap();
+                for len in 0..cipher.len() {
+                    assert!(sk.decrypt(&cipher[..len], compressed, model).is_err());
+                }
+                // the layout is not self-describing
+                assert!(sk.decrypt(&cipher, !compressed, model).is_err());
+
+                let mut bad = cipher.clone();
+                bad[0] = 0x05;
+                assert_eq!(
+                    Sm2Error::InvalidCiphertext,
+                    sk.decrypt(&bad, compressed, model).unwrap_err()
+                );
+                let mut bad = cipher.clone();
+                bad.push(0);
+                assert!(sk.decrypt(&bad, compressed, model).is_err());
+                let mut bad = cipher.clone();
+                bad[1..33].copy_from_slice(&[0xff; 32]);
+                assert!(sk.decrypt(&bad, compressed, model).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_decrypt_asn1_malformed() {
+        fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+            let mut out = vec![tag];
+            if content.len() < 0x80 {
+                out.push(content.len() as u8);
+            } else {
+                out.extend_from_slice(&[0x81, content.len() as u8]);
+            }
+            out.extend_from_slice(content);
+            out
+        }
+        fn integer(be: &[u8]) -> Vec<u8> {
+            let mut v: Vec<u8> = be.iter().copied().skip_while(|b| *b == 0).collect();
+            if v.is_empty() || v[0] & 0x80 != 0 {
+                v.insert(0, 0);
+            }
+            v
+        }
+        fn sm2_cipher(x: &[u8], y: &[u8], c3: &[u8], c2: &[u8]) -> Vec<u8> {
+            let mut body = der(0x02, x);
+            body.extend_from_slice(&der(0x02, y));
+            body.extend_from_slice(&der(0x04, c3));
+            body.extend_from_slice(&der(0x04, c2));
+            der(0x30, &body)
+        }
+
+        let (pk, sk) = gen_keypair().unwrap();
+        let msg = b"attack at dawn";
+        let raw = pk.encrypt(msg, false, Sm2Model::C1C3C2).unwrap();
+        let (x, y, c3, c2) = (&raw[1..33], &raw[33..65], &raw[65..97], &raw[97..]);
+        let cipher = sm2_cipher(&integer(x), &integer(y), c3, c2);
+        assert_eq!(msg.to_vec(), sk.decrypt_der(&cipher).unwrap());
+        let ours = pk.encrypt_der(msg).unwrap();
+        assert_eq!(msg.to_vec(), sk.decrypt_der(&ours).unwrap());
+
+        for len in 0..cipher.len() {
+            assert!(sk.decrypt_der(&cipher[..len]).is_err());
+        }
+        let mut trailing = cipher.clone();
+        trailing.push(0);
+        assert_eq!(
+            Sm2Error::InvalidDer,
+            sk.decrypt_der(&trailing).unwrap_err()
+        );
+
+        let mut padded = vec![0];
+        padded.extend_from_slice(&integer(x));
+        let mut negative = integer(x);
+        if negative[0] == 0 {
+            negative.remove(0);
+        } else {
+            negative[0] |= 0x80;
+        }
+        let mut too_long = vec![0x01];
+        too_long.extend_from_slice(x);
+        for bad in [
+            sm2_cipher(&padded, &integer(y), c3, c2),
+            sm2_cipher(&negative, &integer(y), c3, c2),
+            sm2_cipher(&too_long, &integer(y), c3, c2),
+            sm2_cipher(&integer(x), &integer(y), &c3[..31], c2),
+            sm2_cipher(&integer(x), &integer(y), c3, &[]),
+            der(0x30, &[]),
+            vec![],
+        ] {
+            assert!(sk.decrypt_der(&bad).is_err());
+        }
+    }
+
+    /// Replay the fuzz regression corpus the same way as the targets in `fuzz/`.
+    #[test]
+    fn test_fuzz_corpus() {
+        let corpus = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus");
+        let sk = Sm2PrivateKey::new(&[0x11; 32]).unwrap();
+        let read = |target: &str| {
+            let mut files: Vec<_> = std::fs::read_dir(corpus.join(target))
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect();
+            files.sort();
+            files
+                .into_iter()
+                .map(|path| {
+                    let name = path.file_name().unwrap().to_str().unwrap().to_owned();
+                    (name, std::fs::read(path).unwrap())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (name, data) in read("decrypt") {
+            let Some((&mode, ciphertext)) = data.split_first() else {
+                continue;
+            };
+            let model = match mode & 0x02 {
+                0 => Sm2Model::C1C2C3,
+                _ => Sm2Model::C1C3C2,
+            };
+            let plain = sk.decrypt(ciphertext, mode & 0x01 != 0, model);
+            assert_eq!(name.starts_with("valid"), plain.is_ok(), "{}", name);
+        }
+        for (name, data) in read("decrypt_asn1") {
+            let plain = sk.decrypt_der(&data);
+            assert_eq!(name.starts_with("valid"), plain.is_ok(), "{}", name);
+        }
     }
 }
@@ -1,8 +1,10 @@
+use zeroize::Zeroize;
+
 use crate::error::{Sm2Error, Sm2Result};
 use crate::fields::fp64::{fp_sqrt, fp_from_mont, fp_to_mont, SM2_P};
 use crate::fields::FieldModOperation;
 use crate::sm2p256_table::SM2P256_PRECOMPUTED;
-use crate::u256::{u256_from_be_bytes, SM2_ZERO, U256};
+use crate::u256::{ct_eq_u64, u256_cmp, u256_from_be_bytes, u256_select, SM2_ZERO, U256};
 
 #[derive(Debug, Clone, Eq, PartialEq, Copy)]
 pub struct Point {
@@ -11,6 +13,14 @@ pub struct Point {
     pub z: U256,
 }
 
+impl Zeroize for Point {
+    fn zeroize(&mut self) {
+        self.x.zeroize();
+        self.y.zeroize();
+        self.z.zeroize();
+    }
+}
+
 impl Point {
     pub fn zero() -> Point {
         Point {
@@ -87,13 +97,23 @@ impl Point {
         ret
     }
 
+    /// Parse a compressed (`02`/`03`) or uncompressed (`04`) SEC1 point.
+    ///
+    /// Coordinates must be below `p`; whether an uncompressed point lies on the
+    /// curve is left to the caller.
     pub(crate) fn from_byte(b: &[u8]) -> Sm2Result<Point> {
-        let flag = b[0];
+        let flag = match b.first() {
+            Some(flag) => *flag,
+            None => return Err(Sm2Error::InvalidPublic),
+        };
         // Compressed Point
         if flag == 0x02 || flag == 0x03 {
             if b.len() != 33 {
                 return Err(Sm2Error::InvalidPublic);
             }
+            if u256_cmp(&u256_from_be_bytes(&b[1..]), &SM2_P) >= 0 {
+                return Err(Sm2Error::InvalidPublic);
+            }
             let y_q;
             if b[0] == 0x02 {
                 y_q = 0;
@@ -120,11 +140,16 @@ impl Point {
         }
         // uncompressed Point
         else {
-            if b.len() != 65 {
+            if flag != 0x04 || b.len() != 65 {
                 return Err(Sm2Error::InvalidPublic);
             }
-            let x = fp_to_mont(&u256_from_be_bytes(&b[1..33]));
-            let y = fp_to_mont(&u256_from_be_bytes(&b[33..65]));
+            let x = u256_from_be_bytes(&b[1..33]);
+            let y = u256_from_be_bytes(&b[33..65]);
+            if u256_cmp(&x, &SM2_P) >= 0 || u256_cmp(&y, &SM2_P) >= 0 {
+                return Err(Sm2Error::InvalidPublic);
+            }
+            let x = fp_to_mont(&x);
+            let y = fp_to_mont(&y);
             Ok(Point {
                 x,
                 y,
@@ -194,28 +219,8 @@ impl Point {
 
     // P = [k]G
     pub fn scalar_mul(&self, scalar: &[u64]) -> Point {
-        let mut pre_table = vec![];
-        for _ in 0..16 {
-            pre_table.push(Point::zero());
-        }
-
+        let pre_table = self.window_table();
         let mut r = Point::zero();
-        pre_table[1 - 1] = *self;
-        pre_table[2 - 1] = pre_table[1 - 1].point_dbl();
-        pre_table[4 - 1] = pre_table[2 - 1].point_dbl();
-        pre_table[8 - 1] = pre_table[4 - 1].point_dbl();
-        pre_table[3 - 1] = pre_table[1 - 1].point_add(&pre_table[2 - 1]);
-        pre_table[6 - 1] = pre_table[3 - 1].point_dbl();
-        pre_table[7 - 1] = pre_table[1 - 1].point_add(&pre_table[6 - 1]);
-        pre_table[12 - 1] = pre_table[6 - 1].point_dbl();
-        pre_table[5 - 1] = pre_table[1 - 1].point_add(&pre_table[4 - 1]);
-        pre_table[10 - 1] = pre_table[5 - 1].point_dbl();
-        pre_table[14 - 1] = pre_table[7 - 1].point_dbl();
-        pre_table[9 - 1] = pre_table[1 - 1].point_add(&pre_table[8 - 1]);
-        pre_table[11 - 1] = pre_table[1 - 1].point_add(&pre_table[10 - 1]);
-        pre_table[13 - 1] = pre_table[1 - 1].point_add(&pre_table[12 - 1]);
-        pre_table[15 - 1] = pre_table[1 - 1].point_add(&pre_table[14 - 1]);
-
         for i in 0..scalar.len() {
             for j in 0..(64 / 4) {
                 let index = scalar[4 - 1 - i] >> ((64 / 4 - 1 - j) * 4);
@@ -235,6 +240,27 @@ impl Point {
         r
     }
 
+    /// Multiples `[1]P ... [15]P` at index `i - 1`, used by the 4-bit window methods.
+    pub(crate) fn window_table(&self) -> [Point; 16] {
+        let mut pre_table = [Point::zero(); 16];
+        pre_table[1 - 1] = *self;
+        pre_table[2 - 1] = pre_table[1 - 1].point_dbl();
+        pre_table[4 - 1] = pre_table[2 - 1].point_dbl();
+        pre_table[8 - 1] = pre_table[4 - 1].point_dbl();
+        pre_table[3 - 1] = pre_table[1 - 1].point_add(&pre_table[2 - 1]);
+        pre_table[6 - 1] = pre_table[3 - 1].point_dbl();
+        pre_table[7 - 1] = pre_table[1 - 1].point_add(&pre_table[6 - 1]);
+        pre_table[12 - 1] = pre_table[6 - 1].point_dbl();
+        pre_table[5 - 1] = pre_table[1 - 1].point_add(&pre_table[4 - 1]);
+        pre_table[10 - 1] = pre_table[5 - 1].point_dbl();
+        pre_table[14 - 1] = pre_table[7 - 1].point_dbl();
+        pre_table[9 - 1] = pre_table[1 - 1].point_add(&pre_table[8 - 1]);
+        pre_table[11 - 1] = pre_table[1 - 1].point_add(&pre_table[10 - 1]);
+        pre_table[13 - 1] = pre_table[1 - 1].point_add(&pre_table[12 - 1]);
+        pre_table[15 - 1] = pre_table[1 - 1].point_add(&pre_table[14 - 1]);
+        pre_table
+    }
+
     pub fn point_dbl(&self) -> Point {
         let x1 = self.x;
         let y1 = self.y;
@@ -279,6 +305,269 @@ pub fn g_mul(g: &U256) -> Point {
     r
 }
 
+/// `[k1]P1 + [k2]P2 + ...` with Straus' interleaved method, the doublings are
+/// shared by all terms.
+pub fn multi_scalar_mul(points: &[Point], scalars: &[U256]) -> Point {
+    let tables: Vec<[Point; 16]> = points.iter().map(|p| p.window_table()).collect();
+    multi_scalar_mul_tables(&tables, scalars)
+}
+
+/// [`multi_scalar_mul`] with the window tables of the points already built.
+pub(crate) fn multi_scalar_mul_tables(tables: &[[Point; 16]], scalars: &[U256]) -> Point {
+    assert_eq!(tables.len(), scalars.len());
+    let mut r = Point::zero();
+    for w in (0..64).rev() {
+        if !r.is_zero() {
+            r = r.point_dbl().point_dbl().point_dbl().point_dbl();
+        }
+        for (table, k) in tables.iter().zip(scalars.iter()) {
+            let index = (k[w / 16] >> ((w % 16) * 4)) & 0x0f;
+            if index != 0 {
+                r = table[(index - 1) as usize].point_add(&r);
+            }
+        }
+    }
+    r
+}
+
+/// Convert points to affine coordinates with a single field inversion
+/// (Montgomery's trick). Points at infinity are returned unchanged.
+pub fn batch_to_affine(points: &[Point]) -> Vec<Point> {
+    let one = crate::fields::fp64::SM2_MODP_MONT_ONE;
+    // prefix[i] = z_0 * ... * z_(i-1), skipping points at infinity
+    let mut prefix = Vec::with_capacity(points.len());
+    let mut acc = one;
+    for p in points {
+        prefix.push(acc);
+        if !p.is_zero() {
+            acc = acc.fp_mul(&p.z);
+        }
+    }
+    let mut inv = acc.fp_inv();
+    let mut out = vec![Point::zero(); points.len()];
+    for i in (0..points.len()).rev() {
+        let p = &points[i];
+        if p.is_zero() {
+            continue;
+        }
+        let z_inv = inv.fp_mul(&prefix[i]);
+        inv = inv.fp_mul(&p.z);
+        let z_inv2 = z_inv.fp_sqr();
+        let z_inv3 = z_inv2.fp_mul(&z_inv);
+        out[i] = Point {
+            x: p.x.fp_mul(&z_inv2),
+            y: p.y.fp_mul(&z_inv3),
+            z: one,
+        };
+    }
+    out
+}
+
+// Constant-time path for secret scalars.
+//
+// The points are kept in homogeneous projective coordinates (x = X/Z, y = Y/Z)
+// so the complete addition formulas of Renes, Costello and Batina (a = -3,
+// "Complete addition formulas for prime order elliptic curves", algorithms 4
+// and 6) can be used: they have no special cases for doubling or the point at
+// infinity. Table entries are selected by scanning the whole table with masks.
+
+#[derive(Clone, Copy)]
+struct ProjectivePoint {
+    x: U256,
+    y: U256,
+    z: U256,
+}
+
+impl ProjectivePoint {
+    const IDENTITY: ProjectivePoint = ProjectivePoint {
+        x: SM2_ZERO,
+        y: crate::fields::fp64::SM2_MODP_MONT_ONE,
+        z: SM2_ZERO,
+    };
+
+    // jacobian (X, Y, Z) -> projective (XZ, Y, Z^3)
+    fn from_jacobian(p: &Point) -> ProjectivePoint {
+        let z2 = p.z.fp_sqr();
+        let r = ProjectivePoint {
+            x: p.x.fp_mul(&p.z),
+            y: p.y,
+            z: z2.fp_mul(&p.z),
+        };
+        r.select(&ProjectivePoint::IDENTITY, is_zero_u256(&p.z))
+    }
+
+    // projective (X, Y, Z) -> jacobian (XZ, YZ^2, Z)
+    fn to_jacobian(self) -> Point {
+        let z2 = self.z.fp_sqr();
+        let r = Point {
+            x: self.x.fp_mul(&self.z),
+            y: self.y.fp_mul(&z2),
+            z: self.z,
+        };
+        let zero = Point::zero();
+        let choice = is_zero_u256(&self.z);
+        Point {
+            x: u256_select(&r.x, &zero.x, choice),
+            y: u256_select(&r.y, &zero.y, choice),
+            z: u256_select(&r.z, &zero.z, choice),
+        }
+    }
+
+    fn select(&self, other: &ProjectivePoint, choice: u64) -> ProjectivePoint {
+        ProjectivePoint {
+            x: u256_select(&self.x, &other.x, choice),
+            y: u256_select(&self.y, &other.y, choice),
+            z: u256_select(&self.z, &other.z, choice),
+        }
+    }
+
+    // algorithm 4
+    fn add(&self, p: &ProjectivePoint) -> ProjectivePoint {
+        let b = &crate::fields::fp64::SM2_MODP_MONT_B;
+        let (x1, y1, z1) = (&self.x, &self.y, &self.z);
+        let (x2, y2, z2) = (&p.x, &p.y, &p.z);
+
+        let mut t0 = x1.fp_mul(x2);
+        let mut t1 = y1.fp_mul(y2);
+        let mut t2 = z1.fp_mul(z2);
+        let mut t3 = x1.fp_add(y1);
+        let mut t4 = x2.fp_add(y2);
+        t3 = t3.fp_mul(&t4);
+        t4 = t0.fp_add(&t1);
+        t3 = t3.fp_sub(&t4);
+        t4 = y1.fp_add(z1);
+        let mut x3 = y2.fp_add(z2);
+        t4 = t4.fp_mul(&x3);
+        x3 = t1.fp_add(&t2);
+        t4 = t4.fp_sub(&x3);
+        x3 = x1.fp_add(z1);
+        let mut y3 = x2.fp_add(z2);
+        x3 = x3.fp_mul(&y3);
+        y3 = t0.fp_add(&t2);
+        y3 = x3.fp_sub(&y3);
+        let mut z3 = b.fp_mul(&t2);
+        x3 = y3.fp_sub(&z3);
+        z3 = x3.fp_add(&x3);
+        x3 = x3.fp_add(&z3);
+        z3 = t1.fp_sub(&x3);
+        x3 = t1.fp_add(&x3);
+        y3 = b.fp_mul(&y3);
+        t1 = t2.fp_add(&t2);
+        t2 = t1.fp_add(&t2);
+        y3 = y3.fp_sub(&t2);
+        y3 = y3.fp_sub(&t0);
+        t1 = y3.fp_add(&y3);
+        y3 = t1.fp_add(&y3);
+        t1 = t0.fp_add(&t0);
+        t0 = t1.fp_add(&t0);
+        t0 = t0.fp_sub(&t2);
+        t1 = t4.fp_mul(&y3);
+        t2 = t0.fp_mul(&y3);
+        y3 = x3.fp_mul(&z3);
+        y3 = y3.fp_add(&t2);
+        x3 = t3.fp_mul(&x3);
+        x3 = x3.fp_sub(&t1);
+        z3 = t4.fp_mul(&z3);
+        t1 = t3.fp_mul(&t0);
+        z3 = z3.fp_add(&t1);
+        ProjectivePoint { x: x3, y: y3, z: z3 }
+    }
+
+    // algorithm 6
+    fn double(&self) -> ProjectivePoint {
+        let b = &crate::fields::fp64::SM2_MODP_MONT_B;
+        let (x, y, z) = (&self.x, &self.y, &self.z);
+
+        let mut t0 = x.fp_sqr();
+        let t1 = y.fp_sqr();
+        let mut t2 = z.fp_sqr();
+        let mut t3 = x.fp_mul(y);
+        t3 = t3.fp_add(&t3);
+        let mut z3 = x.fp_mul(z);
+        z3 = z3.fp_add(&z3);
+        let mut y3 = b.fp_mul(&t2);
+        y3 = y3.fp_sub(&z3);
+        let mut x3 = y3.fp_add(&y3);
+        y3 = x3.fp_add(&y3);
+        x3 = t1.fp_sub(&y3);
+        y3 = t1.fp_add(&y3);
+        y3 = x3.fp_mul(&y3);
+        x3 = x3.fp_mul(&t3);
+        t3 = t2.fp_add(&t2);
+        t2 = t2.fp_add(&t3);
+        z3 = b.fp_mul(&z3);
+        z3 = z3.fp_sub(&t2);
+        z3 = z3.fp_sub(&t0);
+        t3 = z3.fp_add(&z3);
+        z3 = z3.fp_add(&t3);
+        t3 = t0.fp_add(&t0);
+        t0 = t3.fp_add(&t0);
+        t0 = t0.fp_sub(&t2);
+        t0 = t0.fp_mul(&z3);
+        y3 = y3.fp_add(&t0);
+        t0 = y.fp_mul(z);
+        t0 = t0.fp_add(&t0);
+        z3 = t0.fp_mul(&z3);
+        x3 = x3.fp_sub(&z3);
+        z3 = t0.fp_mul(&t1);
+        z3 = z3.fp_add(&z3);
+        z3 = z3.fp_add(&z3);
+        ProjectivePoint { x: x3, y: y3, z: z3 }
+    }
+}
+
+fn is_zero_u256(a: &U256) -> u64 {
+    ct_eq_u64(a[0] | a[1] | a[2] | a[3], 0)
+}
+
+impl Point {
+    /// Constant-time `[scalar]self` for secret scalars: a fixed 4-bit window
+    /// ladder over all 64 windows, masked table lookups and complete addition.
+    pub fn scalar_mul_ct(&self, scalar: &U256) -> Point {
+        let mut table = [ProjectivePoint::IDENTITY; 16];
+        table[1] = ProjectivePoint::from_jacobian(self);
+        for i in 2..16 {
+            table[i] = table[i - 1].add(&table[1]);
+        }
+
+        let mut r = ProjectivePoint::IDENTITY;
+        for w in (0..64).rev() {
+            r = r.double().double().double().double();
+            let index = (scalar[w / 16] >> ((w % 16) * 4)) & 0x0f;
+            let mut t = ProjectivePoint::IDENTITY;
+            for (i, entry) in table.iter().enumerate() {
+                t = t.select(entry, ct_eq_u64(i as u64, index));
+            }
+            r = r.add(&t);
+        }
+        r.to_jacobian()
+    }
+}
+
+/// Constant-time `[k]G` for secret scalars. Uses the same comb table as
+/// [`g_mul`], but every one of the 255 entries of each row is read.
+pub fn g_mul_ct(k: &U256) -> Point {
+    let one = crate::fields::fp64::SM2_MODP_MONT_ONE;
+    let mut r = ProjectivePoint::IDENTITY;
+    for (index, scalar_word) in k.iter().enumerate() {
+        for m in 0..8 {
+            let row = &SM2P256_PRECOMPUTED[8 * index + m];
+            let byte = (scalar_word >> (8 * m)) & 0xff;
+            let mut t = ProjectivePoint::IDENTITY;
+            for i in 1..256 {
+                let entry = ProjectivePoint {
+                    x: row[i * 2 - 2],
+                    y: row[i * 2 - 1],
+                    z: one,
+                };
+                t = t.select(&entry, ct_eq_u64(i as u64, byte));
+            }
+            r = r.add(&t);
+        }
+    }
+    r.to_jacobian()
+}
+
 pub(crate) fn to_jacobi(x: &U256, y: &U256) -> Point {
     let mut r = Point::zero();
     r.x.copy_from_slice(x);
@@ -289,9 +578,10 @@ pub(crate) fn to_jacobi(x: &U256, y: &U256) -> Point {
 
 #[cfg(test)]
 mod test {
+    use crate::fields::fn64::SM2_N;
     use crate::fields::fp64::fp_to_mont;
-    use crate::p256_ecc::{g_mul, to_jacobi, Point};
-    use crate::u256::u256_from_be_bytes;
+    use crate::p256_ecc::{g_mul, g_mul_ct, to_jacobi, Point, ProjectivePoint};
+    use crate::u256::{u256_from_be_bytes, u256_sub, U256};
 
     #[test]
     fn test_mod_op() {
@@ -401,4 +691,61 @@ mod test {
             r.to_affine_point().is_valid_affine_point()
         );
     }
+
+    fn assert_same_point(a: &Point, b: &Point) {
+        assert_eq!(a.is_zero(), b.is_zero());
+        if !a.is_zero() {
+            assert_eq!(a.to_affine_point(), b.to_affine_point());
+        }
+    }
+
+    fn test_scalars() -> Vec<U256> {
+        let mut scalars = vec![
+            [0, 0, 0, 0],
+            [1, 0, 0, 0],
+            [2, 0, 0, 0],
+            [15, 0, 0, 0],
+            [16, 0, 0, 0],
+            [0, 0, 0, 1 << 63],
+            [u64::MAX, u64::MAX, u64::MAX, u64::MAX],
+            u256_sub(&SM2_N, &[1, 0, 0, 0]).0,
+            SM2_N,
+        ];
+        for _ in 0..16 {
+            scalars.push(crate::fields::fp64::random_u256_with_rng(&mut rand::thread_rng()));
+        }
+        scalars
+    }
+
+    #[test]
+    fn test_g_mul_ct() {
+        for k in test_scalars() {
+            assert_same_point(&g_mul_ct(&k), &g_mul(&k));
+        }
+    }
+
+    #[test]
+    fn test_scalar_mul_ct() {
+        let p = g_mul(&[0x1234_5678, 0x9abc, 0xdef0, 0x42]);
+        for k in test_scalars() {
+            assert_same_point(&p.scalar_mul_ct(&k), &p.scalar_mul(&k));
+        }
+        assert!(Point::zero().scalar_mul_ct(&[5, 0, 0, 0]).is_zero());
+    }
+
+    #[test]
+    fn test_complete_add() {
+        let p = ProjectivePoint::from_jacobian(&g_mul(&[3, 0, 0, 0]));
+        let q = ProjectivePoint::from_jacobian(&g_mul(&[3, 0, 0, 0]).neg());
+        let o = ProjectivePoint::IDENTITY;
+
+        assert_same_point(&p.to_jacobian(), &g_mul(&[3, 0, 0, 0]));
+        assert_same_point(&p.add(&p).to_jacobian(), &g_mul(&[6, 0, 0, 0]));
+        assert_same_point(&p.double().to_jacobian(), &g_mul(&[6, 0, 0, 0]));
+        assert!(p.add(&q).to_jacobian().is_zero());
+        assert_same_point(&p.add(&o).to_jacobian(), &g_mul(&[3, 0, 0, 0]));
+        assert_same_point(&o.add(&p).to_jacobian(), &g_mul(&[3, 0, 0, 0]));
+        assert!(o.add(&o).to_jacobian().is_zero());
+        assert!(o.double().to_jacobian().is_zero());
+    }
 }
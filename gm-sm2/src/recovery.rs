@@ -0,0 +1,236 @@
+//! Public key recovery from SM2 signatures.
+//!
+//! Given `r || s`, the digest `e` and a two-bit recovery id, the signer's point
+//! `R = [k]G` is rebuilt from `x1 = r - e mod n` and the public key follows from
+//! `s = (1 + d)^-1 (k - r d)`:
+//!
+//! ```text
+//! P = [(s + r)^-1](R - [s]G)
+//! ```
+//!
+//! The recovery id is returned by [`Sm2PrivateKey::sign_recoverable`]: bit 0 is
+//! the parity of `y1`, bit 1 is set when `x1 >= n` (probability about 2^-96).
+//!
+//! Unlike ECDSA, the SM2 digest is `e = SM3(ZA || M)` where `ZA` hashes the
+//! public key itself, so a key cannot be recovered from the message alone: the
+//! verifier needs `e`. Either transmit `e` (32 bytes instead of a 33 or 65 byte
+//! key) and check it with [`recover_and_verify`], which recomputes
+//! `SM3(ZA || M)` with the recovered key, or sign a digest that does not depend
+//! on the key with [`Sm2PrivateKey::sign_digest_recoverable`] and call
+//! [`recover_public_key`] directly. In the second case the signature is no
+//! longer a standard SM2 signature over `M` and the key must still be matched
+//! against a trusted list by the application.
+//!
+//! [`Sm2PrivateKey::sign_recoverable`]: crate::key::Sm2PrivateKey::sign_recoverable
+//! [`Sm2PrivateKey::sign_digest_recoverable`]: crate::key::Sm2PrivateKey::sign_digest_recoverable
+
+use crate::error::{Sm2Error, Sm2Result};
+use crate::fields::fn64::{fn_add, fn_mul, fn_pow, fn_sub, SM2_N, SM2_N_MINUS_TWO};
+use crate::fields::fp64::SM2_P;
+use crate::fields::FieldModOperation;
+use crate::key::Sm2PublicKey;
+use crate::p256_ecc::{g_mul, Point};
+use crate::u256::{u256_add, u256_cmp, u256_from_be_bytes, u256_sub};
+
+/// Recover the public key from a precomputed digest `e`, an `r || s` signature
+/// and its recovery id.
+///
+/// The recovered key is checked against the signature before it is returned.
+/// That does not pin down the key: flipping bit 0 of `recid` recovers the key
+/// for `-R`, which the signature verifies under as well. Trust comes from
+/// matching the result against a known key or its certificate.
+///
+/// # Example
+/// ```rust
+/// use gm_sm2::key::gen_keypair;
+/// use gm_sm2::recovery::recover_public_key;
+/// fn main(){
+///     let (pk, sk) = gen_keypair().unwrap();
+///     let mut hasher = pk.za_hasher(None).unwrap();
+///     hasher.update(b"hello");
+///     let e = hasher.finalize();
+///     let (sig, recid) = sk.sign_digest_recoverable(&e).unwrap();
+///     let recovered = recover_public_key(&e, &sig, recid).unwrap();
+///     assert_eq!(pk.to_bytes(true), recovered.to_bytes(true));
+/// }
+/// ```
+pub fn recover_public_key(e: &[u8], sig: &[u8], recid: u8) -> Sm2Result<Sm2PublicKey> {
+    if e.len() != 32 {
+        return Err(Sm2Error::InvalidDigestLen);
+    }
+    if sig.len() != 64 {
+        return Err(Sm2Error::InvalidSignature);
+    }
+    if recid > 3 {
+        return Err(Sm2Error::InvalidRecoveryId);
+    }
+    let n = &SM2_N;
+    let r = u256_from_be_bytes(&sig[..32]);
+    let s = u256_from_be_bytes(&sig[32..]);
+    if r.is_zero() || s.is_zero() {
+        return Err(Sm2Error::ZeroSig);
+    }
+    if u256_cmp(&r, n) >= 0 || u256_cmp(&s, n) >= 0 {
+        return Err(Sm2Error::InvalidSignature);
+    }
+    let t = fn_add(&r, &s);
+    if t.is_zero() {
+        return Err(Sm2Error::InvalidSignature);
+    }
+
+    let mut e_n = u256_from_be_bytes(e);
+    if u256_cmp(&e_n, n) >= 0 {
+        e_n = u256_sub(&e_n, n).0;
+    }
+    let mut x1 = fn_sub(&r, &e_n);
+    if recid & 0x02 != 0 {
+        let (x, carry) = u256_add(&x1, n);
+        if carry || u256_cmp(&x, &SM2_P) >= 0 {
+            return Err(Sm2Error::InvalidRecoveryId);
+        }
+        x1 = x;
+    }
+    let mut compressed = [0u8; 33];
+    compressed[0] = 0x02 | (recid & 0x01);
+    compressed[1..].copy_from_slice(&x1.to_byte_be());
+    let r_point = Point::from_byte(&compressed).map_err(|_| Sm2Error::InvalidRecoveryId)?;
+
+    // P = [t^-1]R - [t^-1 s]G
+    let t_inv = fn_pow(&t, &SM2_N_MINUS_TWO);
+    let p = r_point
+        .scalar_mul(&t_inv)
+        .point_add(&g_mul(&fn_mul(&t_inv, &s)).neg());
+    if p.is_zero() {
+        return Err(Sm2Error::InvalidRecoveryId);
+    }
+    let pk = Sm2PublicKey {
+        point: p.to_affine_point(),
+    };
+    // also catches the degenerate cases of the incomplete point addition
+    pk.verify_digest(e, sig)
+        .map_err(|_| Sm2Error::InvalidRecoveryId)?;
+    Ok(pk)
+}
+
+/// Recover the public key of a standard SM2 signature over `msg` and check that
+/// the transmitted digest `e` really is `SM3(ZA || msg)` for that key and `id`.
+///
+/// A `None` id means the default ID "1234567812345678".
+///
+/// # Example
+/// ```rust
+/// use gm_sm2::key::gen_keypair;
+/// use gm_sm2::recovery::recover_and_verify;
+/// fn main(){
+///     let (pk, sk) = gen_keypair().unwrap();
+///     let (sig, recid) = sk.sign_recoverable(Some(b"alice"), b"hello").unwrap();
+///     let mut hasher = pk.za_hasher(Some(b"alice")).unwrap();
+///     hasher.update(b"hello");
+///     let e = hasher.finalize();
+///
+///     // the receiver only gets (msg, e, sig, recid)
+///     let recovered = recover_and_verify(Some(b"alice"), b"hello", &e, &sig, recid).unwrap();
+///     assert_eq!(pk.to_bytes(true), recovered.to_bytes(true));
+///     assert!(recover_and_verify(None, b"hello", &e, &sig, recid).is_err());
+/// }
+/// ```
+pub fn recover_and_verify(
+    id: Option<&[u8]>,
+    msg: &[u8],
+    e: &[u8],
+    sig: &[u8],
+    recid: u8,
+) -> Sm2Result<Sm2PublicKey> {
+    let pk = recover_public_key(e, sig, recid)?;
+    let mut hasher = pk.za_hasher(id)?;
+    hasher.update(msg);
+    if hasher.finalize()[..] != e[..] {
+        return Err(Sm2Error::InvalidDigest);
+    }
+    Ok(pk)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::Sm2Error;
+    use crate::key::{gen_keypair, Sm2PrivateKey};
+    use crate::recovery::{recover_and_verify, recover_public_key};
+
+    #[test]
+    fn test_recover_public_key() {
+        for i in 0..8u8 {
+            let (pk, sk) = gen_keypair().unwrap();
+            let msg = vec![i; i as usize * 13];
+            let (sig, recid) = sk.sign_recoverable(None, &msg).unwrap();
+            assert!(recid < 4);
+            pk.verify(None, &msg, &sig).unwrap();
+
+            let mut hasher = pk.za_hasher(None).unwrap();
+            hasher.update(&msg);
+            let e = hasher.finalize();
+            let recovered = recover_public_key(&e, &sig, recid).unwrap();
+            assert_eq!(pk.to_bytes(false), recovered.to_bytes(false));
+            let recovered = recover_and_verify(None, &msg, &e, &sig, recid).unwrap();
+            assert_eq!(pk.to_bytes(false), recovered.to_bytes(false));
+
+            // the other y parity belongs to -R: the signature verifies under
+            // that key too, but ZA no longer matches the digest
+            let other = recover_public_key(&e, &sig, recid ^ 1).unwrap();
+            assert_ne!(pk.to_bytes(false), other.to_bytes(false));
+            other.verify_digest(&e, &sig).unwrap();
+            assert_eq!(
+                Sm2Error::InvalidDigest,
+                recover_and_verify(None, &msg, &e, &sig, recid ^ 1).unwrap_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_recover_digest() {
+        let sk = Sm2PrivateKey::new(&[0x5a; 32]).unwrap();
+        let e = [0xab; 32];
+        let (sig, recid) = sk.sign_digest_recoverable(&e).unwrap();
+        let recovered = recover_public_key(&e, &sig, recid).unwrap();
+        assert_eq!(sk.to_public_key().to_bytes(true), recovered.to_bytes(true));
+
+        // the digest is bound to the recovered key through ZA
+        assert_eq!(
+            Sm2Error::InvalidDigest,
+            recover_and_verify(None, b"", &e, &sig, recid).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_recover_invalid() {
+        let (_, sk) = gen_keypair().unwrap();
+        let e = [0x11; 32];
+        let (sig, recid) = sk.sign_digest_recoverable(&e).unwrap();
+
+        assert_eq!(
+            Sm2Error::InvalidRecoveryId,
+            recover_public_key(&e, &sig, 4).unwrap_err()
+        );
+        assert_eq!(
+            Sm2Error::InvalidDigestLen,
+            recover_public_key(&e[..31], &sig, recid).unwrap_err()
+        );
+        assert_eq!(
+            Sm2Error::InvalidSignature,
+            recover_public_key(&e, &sig[..63], recid).unwrap_err()
+        );
+        assert_eq!(
+            Sm2Error::ZeroSig,
+            recover_public_key(&e, &[0; 64], recid).unwrap_err()
+        );
+        assert_eq!(
+            Sm2Error::InvalidSignature,
+            recover_public_key(&e, &[0xff; 64], recid).unwrap_err()
+        );
+
+        // a different digest recovers a different key, or none at all
+        let other = [0x12; 32];
+        if let Ok(pk) = recover_public_key(&other, &sig, recid) {
+            assert_ne!(sk.to_public_key().to_bytes(true), pk.to_bytes(true));
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Deterministic nonce generation for SM2 signatures.
+//!
+//! Follows RFC 6979 section 3.2 with HMAC-SM3 as the HMAC_DRBG hash. Both the
+//! group order `n` and the SM3 output are 256 bits wide, so `bits2int` is the
+//! identity and `bits2octets` is a single conditional subtraction of `n`.
+
+use gm_sm3::{hmac_sm3, HmacSm3};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::fields::fn64::SM2_N;
+use crate::fields::FieldModOperation;
+use crate::u256::{u256_cmp, u256_from_be_bytes, u256_sub, U256};
+
+pub(crate) struct NonceGenerator {
+    k: [u8; 32],
+    v: [u8; 32],
+}
+
+impl NonceGenerator {
+    /// Seed the generator with the private key `d`, the digest `e` and optional
+    /// `extra` data (empty for fully deterministic signatures, fresh randomness
+    /// for hedged signatures).
+    pub(crate) fn new(d: &U256, e: &[u8], extra: &[u8]) -> NonceGenerator {
+        let x = Zeroizing::new(d.to_byte_be());
+        let mut h = u256_from_be_bytes(e);
+        if u256_cmp(&h, &SM2_N) >= 0 {
+            h = u256_sub(&h, &SM2_N).0;
+        }
+        let h = h.to_byte_be();
+
+        let mut gen = NonceGenerator {
+            k: [0x00; 32],
+            v: [0x01; 32],
+        };
+        for round in [0x00u8, 0x01] {
+            let mut mac = HmacSm3::new(&gen.k);
+            mac.update(&gen.v);
+            mac.update(&[round]);
+            mac.update(&x);
+            mac.update(&h);
+            mac.update(extra);
+            gen.k = mac.finalize();
+            gen.v = hmac_sm3(&gen.k, &gen.v);
+        }
+        gen
+    }
+
+    /// Return the next candidate `k` in [1, n-1]. Calling it again after a
+    /// rejected signature continues the generation as RFC 6979 step h.3.
+    pub(crate) fn next_k(&mut self) -> U256 {
+        loop {
+            self.v = hmac_sm3(&self.k, &self.v);
+            let k = u256_from_be_bytes(&self.v);
+            self.reseed();
+            if !k.is_zero() && u256_cmp(&k, &SM2_N) < 0 {
+                return k;
+            }
+        }
+    }
+
+    // K = HMAC_K(V || 0x00), V = HMAC_K(V)
+    fn reseed(&mut self) {
+        let mut mac = HmacSm3::new(&self.k);
+        mac.update(&self.v);
+        mac.update(&[0x00]);
+        self.k = mac.finalize();
+        self.v = hmac_sm3(&self.k, &self.v);
+    }
+}
+
+impl Drop for NonceGenerator {
+    fn drop(&mut self) {
+        self.k.zeroize();
+        self.v.zeroize();
+    }
+}
@@ -0,0 +1,377 @@
+use pkcs8::der;
+use pkcs8::der::asn1::{AnyRef, BitString};
+use pkcs8::spki::{AlgorithmIdentifier, SignatureAlgorithmIdentifier, SignatureBitStringEncoding};
+use signature::digest::consts::U32;
+use signature::digest::Digest;
+use signature::rand_core::CryptoRngCore;
+use signature::{
+    DigestSigner, DigestVerifier, Keypair, RandomizedSigner, SignatureEncoding, Signer, Verifier,
+};
+
+use crate::error::{Sm2Error, Sm2Result};
+use crate::fields::fn64::SM2_N;
+use crate::fields::FieldModOperation;
+use crate::key::{Sm2PrivateKey, Sm2PublicKey};
+use crate::u256::{u256_cmp, u256_from_be_bytes, U256};
+use crate::OID_SM2_SM3;
+
+/// SM2 signature value `(r, s)`.
+///
+/// Converts between the raw 64-byte `r || s` form returned by [`Sm2PrivateKey::sign`]
+/// and the GM/T 0009 ASN.1 form used by X.509, CMS, BouncyCastle and GmSSL:
+///
+/// ```text
+/// SM2Signature ::= SEQUENCE {
+///     R INTEGER,
+///     S INTEGER
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sm2Signature {
+    r: U256,
+    s: U256,
+}
+
+impl Sm2Signature {
+    /// Build from the 32-byte big-endian `r` and `s`; both must be in [1, n-1].
+    pub fn new(r: &[u8; 32], s: &[u8; 32]) -> Sm2Result<Self> {
+        let r = u256_from_be_bytes(r);
+        let s = u256_from_be_bytes(s);
+        if r.is_zero() || s.is_zero() {
+            return Err(Sm2Error::ZeroSig);
+        }
+        if u256_cmp(&r, &SM2_N) >= 0 || u256_cmp(&s, &SM2_N) >= 0 {
+            return Err(Sm2Error::InvalidSignature);
+        }
+        Ok(Self { r, s })
+    }
+
+    pub fn r(&self) -> [u8; 32] {
+        to_array(&self.r)
+    }
+
+    pub fn s(&self) -> [u8; 32] {
+        to_array(&self.s)
+    }
+
+    /// Raw `r || s`
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut out = [0u8; 64];
+        out[..32].copy_from_slice(&self.r());
+        out[32..].copy_from_slice(&self.s());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Sm2Result<Self> {
+        if bytes.len() != 64 {
+            return Err(Sm2Error::InvalidSignature);
+        }
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        r.copy_from_slice(&bytes[..32]);
+        s.copy_from_slice(&bytes[32..]);
+        Self::new(&r, &s)
+    }
+
+    pub fn to_der(&self) -> Vec<u8> {
+        yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                writer.next().write_bigint_bytes(&self.r(), true);
+                writer.next().write_bigint_bytes(&self.s(), true);
+            });
+        })
+    }
+
+    /// Parse strict DER: definite minimal lengths, no negative or zero-padded
+    /// integers, no trailing data.
+    pub fn from_der(der: &[u8]) -> Sm2Result<Self> {
+        let (r, s) = yasna::parse_der(der, |reader| {
+            reader.read_sequence(|reader| {
+                let r = reader.next().read_biguint()?;
+                let s = reader.next().read_biguint()?;
+                Ok((r, s))
+            })
+        })
+        .map_err(|_| Sm2Error::InvalidDer)?;
+        let r = r.to_bytes_be();
+        let s = s.to_bytes_be();
+        if r.len() > 32 || s.len() > 32 {
+            return Err(Sm2Error::InvalidSignature);
+        }
+        let mut r_buf = [0u8; 32];
+        let mut s_buf = [0u8; 32];
+        r_buf[32 - r.len()..].copy_from_slice(&r);
+        s_buf[32 - s.len()..].copy_from_slice(&s);
+        Self::new(&r_buf, &s_buf)
+    }
+}
+
+fn to_array(a: &U256) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&a.to_byte_be());
+    out
+}
+
+impl Sm2PrivateKey {
+    /// Sign the given message and return the DER encoded `SM2Signature`.
+    pub fn sign_der(&self, id: Option<&[u8]>, msg: &[u8]) -> Sm2Result<Vec<u8>> {
+        let sig = self.sign(id, msg)?;
+        Ok(Sm2Signature::from_bytes(&sig)?.to_der())
+    }
+}
+
+impl Sm2PublicKey {
+    /// Verify a DER encoded `SM2Signature` over the given message.
+    pub fn verify_der(&self, id: Option<&[u8]>, msg: &[u8], der: &[u8]) -> Sm2Result<()> {
+        let sig = Sm2Signature::from_der(der)?;
+        self.verify(id, msg, &sig.to_bytes())
+    }
+}
+
+// RustCrypto `signature` traits. Message based traits use the default ID
+// "1234567812345678"; digest based traits expect a hasher that already absorbed
+// ZA, such as the one returned by `Sm2PublicKey::za_hasher`.
+
+impl TryFrom<&[u8]> for Sm2Signature {
+    type Error = signature::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Sm2Signature::from_bytes(bytes).map_err(signature::Error::from_source)
+    }
+}
+
+impl From<Sm2Signature> for [u8; 64] {
+    fn from(sig: Sm2Signature) -> Self {
+        sig.to_bytes()
+    }
+}
+
+impl SignatureEncoding for Sm2Signature {
+    type Repr = [u8; 64];
+}
+
+impl SignatureBitStringEncoding for Sm2Signature {
+    /// X.509 and CMS carry the DER `SM2Signature` inside the BIT STRING.
+    fn to_bitstring(&self) -> der::Result<BitString> {
+        BitString::from_bytes(&self.to_der())
+    }
+}
+
+impl SignatureAlgorithmIdentifier for Sm2PrivateKey {
+    type Params = AnyRef<'static>;
+
+    const SIGNATURE_ALGORITHM_IDENTIFIER: AlgorithmIdentifier<Self::Params> = AlgorithmIdentifier {
+        oid: OID_SM2_SM3,
+        parameters: None,
+    };
+}
+
+impl SignatureAlgorithmIdentifier for Sm2PublicKey {
+    type Params = AnyRef<'static>;
+
+    const SIGNATURE_ALGORITHM_IDENTIFIER: AlgorithmIdentifier<Self::Params> = AlgorithmIdentifier {
+        oid: OID_SM2_SM3,
+        parameters: None,
+    };
+}
+
+impl Keypair for Sm2PrivateKey {
+    type VerifyingKey = Sm2PublicKey;
+
+    fn verifying_key(&self) -> Sm2PublicKey {
+        self.public_key
+    }
+}
+
+/// Deterministic (RFC 6979) signature, like [`Sm2PrivateKey::sign_deterministic`].
+impl Signer<Sm2Signature> for Sm2PrivateKey {
+    fn try_sign(&self, msg: &[u8]) -> Result<Sm2Signature, signature::Error> {
+        let sig = self
+            .sign_deterministic(None, msg)
+            .map_err(signature::Error::from_source)?;
+        Sm2Signature::try_from(&sig[..])
+    }
+}
+
+impl RandomizedSigner<Sm2Signature> for Sm2PrivateKey {
+    fn try_sign_with_rng(
+        &self,
+        rng: &mut impl CryptoRngCore,
+        msg: &[u8],
+    ) -> Result<Sm2Signature, signature::Error> {
+        let sig = self
+            .sign_with_rng(rng, None, msg)
+            .map_err(signature::Error::from_source)?;
+        Sm2Signature::try_from(&sig[..])
+    }
+}
+
+/// Sign the SM2 digest `e = SM3(ZA || M)` finished by `digest`.
+///
+/// `digest` must be an SM3 hasher that already absorbed `ZA`, such as the one
+/// returned by [`Sm2PublicKey::za_hasher`]. Any other 32-byte digest, including
+/// a bare SM3 of the message or SHA-256, yields a signature that
+/// [`Sm2PublicKey::verify`] rejects.
+impl<D: Digest<OutputSize = U32>> DigestSigner<D, Sm2Signature> for Sm2PrivateKey {
+    fn try_sign_digest(&self, digest: D) -> Result<Sm2Signature, signature::Error> {
+        let sig = self
+            .sign_digest_hedged_raw(&digest.finalize(), &[])
+            .map_err(signature::Error::from_source)?;
+        Sm2Signature::try_from(&sig[..])
+    }
+}
+
+impl Verifier<Sm2Signature> for Sm2PublicKey {
+    fn verify(&self, msg: &[u8], signature: &Sm2Signature) -> Result<(), signature::Error> {
+        Sm2PublicKey::verify(self, None, msg, &signature.to_bytes())
+            .map_err(signature::Error::from_source)
+    }
+}
+
+/// Verify against the SM2 digest `e = SM3(ZA || M)` finished by `digest`.
+///
+/// As with [`DigestSigner`], `digest` must be an SM3 hasher that already
+/// absorbed `ZA`, see [`Sm2PublicKey::za_hasher`].
+impl<D: Digest<OutputSize = U32>> DigestVerifier<D, Sm2Signature> for Sm2PublicKey {
+    fn verify_digest(&self, digest: D, signature: &Sm2Signature) -> Result<(), signature::Error> {
+        Sm2PublicKey::verify_digest(self, &digest.finalize(), &signature.to_bytes())
+            .map_err(signature::Error::from_source)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use gm_sm3::Sm3;
+    use pkcs8::spki::{DynSignatureAlgorithmIdentifier, SignatureBitStringEncoding};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use signature::{
+        DigestSigner, DigestVerifier, Keypair, RandomizedSigner, SignatureEncoding, Signer,
+        Verifier,
+    };
+
+    use crate::error::Sm2Error;
+    use crate::key::{gen_keypair, Sm2PrivateKey};
+    use crate::signature::Sm2Signature;
+    use crate::OID_SM2_SM3;
+
+    #[test]
+    fn test_der_roundtrip() {
+        let sk = Sm2PrivateKey::from_hex_string(
+            "eb20009ffbffc90aeeb288ca7d782c722332d1d16a206cafec7dd6c64e6fc525",
+        )
+        .unwrap();
+        let raw = sk.sign_deterministic(None, b"hello world").unwrap();
+        let sig = Sm2Signature::from_bytes(&raw).unwrap();
+        let der = sig.to_der();
+        assert_eq!(
+            "3046022100c59da095c36aa05ef6867074945249a09ffe8389d74202f5c2d42d5ed872676d\
+             022100ba9d2cb622da52bf9737af0bbf43519f52e43790f0fbcadfe33f8a961bb594d3",
+            hex::encode(&der)
+        );
+        assert_eq!(sig, Sm2Signature::from_der(&der).unwrap());
+        assert_eq!(raw, sig.to_bytes().to_vec());
+    }
+
+    #[test]
+    fn test_der_short_integers() {
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        r[31] = 1;
+        s[1] = 0x7f;
+        let sig = Sm2Signature::new(&r, &s).unwrap();
+        let der = sig.to_der();
+        assert_eq!(&der[..5], &[0x30, 0x24, 0x02, 0x01, 0x01]);
+        assert_eq!(&der[5..7], &[0x02, 0x1f]);
+        assert_eq!(sig, Sm2Signature::from_der(&der).unwrap());
+    }
+
+    #[test]
+    fn test_der_strict() {
+        let cases: [&str; 7] = [
+            // negative r
+            "3006020180020101",
+            // r padded with a redundant zero byte
+            "300702020001020101",
+            // zero r
+            "3006020100020101",
+            // trailing data
+            "300602010102010100",
+            // long form length where short form is required
+            "308106020101020101",
+            // r longer than 32 bytes
+            "302a0223010000000000000000000000000000000000000000000000000000000000000000020101",
+            // missing s
+            "3003020101",
+        ];
+        for case in cases.iter() {
+            assert!(
+                Sm2Signature::from_der(&hex::decode(case).unwrap()).is_err(),
+                "{}",
+                case
+            );
+        }
+        assert_eq!(
+            Sm2Signature::from_der(&hex::decode("3006020100020101").unwrap()),
+            Err(Sm2Error::ZeroSig)
+        );
+    }
+
+    fn sign_generic<S: Signer<Sm2Signature> + Keypair>(signer: &S, msg: &[u8]) -> Sm2Signature
+    where
+        S::VerifyingKey: Verifier<Sm2Signature>,
+    {
+        let sig = signer.sign(msg);
+        signer.verifying_key().verify(msg, &sig).unwrap();
+        sig
+    }
+
+    #[test]
+    fn test_signature_traits() {
+        let msg = b"hello";
+        let (pk, sk) = gen_keypair().unwrap();
+
+        let sig = sign_generic(&sk, msg);
+        assert_eq!(sig, Signer::sign(&sk, msg));
+        pk.verify(None, msg, &sig.to_bytes()).unwrap();
+        assert!(Verifier::verify(&pk, b"hello!", &sig).is_err());
+
+        let encoded = sig.to_vec();
+        assert_eq!(64, sig.encoded_len());
+        assert_eq!(sig, Sm2Signature::try_from(&encoded[..]).unwrap());
+        assert!(Sm2Signature::try_from(&encoded[..63]).is_err());
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let sig = RandomizedSigner::sign_with_rng(&sk, &mut rng, msg);
+        Verifier::verify(&pk, msg, &sig).unwrap();
+
+        let mut hasher = pk.za_hasher(Some(b"alice")).unwrap();
+        hasher.update(msg);
+        let sig = DigestSigner::<Sm3, Sm2Signature>::sign_digest(&sk, hasher.clone());
+        pk.verify(Some(b"alice"), msg, &sig.to_bytes()).unwrap();
+        DigestVerifier::<Sm3, _>::verify_digest(&pk, hasher, &sig).unwrap();
+        assert!(DigestVerifier::<Sm3, _>::verify_digest(&pk, Sm3::new(), &sig).is_err());
+    }
+
+    #[test]
+    fn test_x509_encoding_traits() {
+        let (_pk, sk) = gen_keypair().unwrap();
+        let sig: Sm2Signature = Signer::sign(&sk, b"tbs certificate");
+        let bits = sig.to_bitstring().unwrap();
+        assert_eq!(sig.to_der(), bits.raw_bytes());
+
+        let alg = sk.signature_algorithm_identifier().unwrap();
+        assert_eq!(OID_SM2_SM3, alg.oid);
+        assert!(alg.parameters.is_none());
+    }
+
+    #[test]
+    fn test_sign_verify_der() {
+        let msg = b"hello";
+        let (pk, sk) = gen_keypair().unwrap();
+        let der = sk.sign_der(None, msg).unwrap();
+        pk.verify_der(None, msg, &der).unwrap();
+        assert!(pk.verify_der(None, b"hello!", &der).is_err());
+        assert!(pk.verify_der(None, msg, &der[..der.len() - 1]).is_err());
+    }
+}
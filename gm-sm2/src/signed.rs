@@ -0,0 +1,604 @@
+//! SM2 signed data: GM/T 0010 `SignedData` with SM3 digests.
+//!
+//! [`sign`] produces a `ContentInfo` of type [`OID_SM2_CMS_SIGNED`] with one
+//! `SignerInfo` per signer:
+//!
+//! ```text
+//! SignedData ::= SEQUENCE {
+//!     version           INTEGER,                     -- 1
+//!     digestAlgorithms  SET OF AlgorithmIdentifier,  -- SM3
+//!     contentInfo       SEQUENCE {
+//!         contentType   OBJECT IDENTIFIER,           -- OID_SM2_CMS_DATA
+//!         content   [0] EXPLICIT OCTET STRING OPTIONAL },
+//!     certificates  [0] IMPLICIT SET OF Certificate,
+//!     signerInfos       SET OF SignerInfo }
+//! ```
+//!
+//! Signers are identified by the issuer and serial number of their embedded
+//! certificate. The signed attributes carry content-type, message-digest
+//! (`SM3(content)`) and optionally signing-time; the signature is a DER
+//! `SM2Signature` over their DER encoding with the default ID
+//! "1234567812345678".
+//!
+//! [`verify`] also accepts what `openssl cms -sign -md sm3` produces for an
+//! SM2 key: the PKCS #7 content types `signedData` and `data` in place of the
+//! GM/T 0010 ones, and signatures made with an empty ID.
+//!
+//! [`verify`] checks every signature against the certificates embedded in the
+//! message. It does not validate the certificates themselves: the caller still
+//! has to build a chain to a trusted root and check validity and key usage.
+//!
+//! # Example
+//! ```rust
+//! use gm_sm2::key::Sm2PrivateKey;
+//! use gm_sm2::signed::{sign, verify, Signer};
+//!
+//! let certificate_der =
+//!     include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sm2_signer.der"));
+//! let sk = Sm2PrivateKey::from_hex_string(
+//!     "17fa99bb179f3a16797b4b167706b6c5a2fa84ee501ecff83140e231852b78ce",
+//! )
+//! .unwrap();
+//! let signer = Signer::from_der(&sk, certificate_der).unwrap();
+//! let signed = sign(&[signer], b"invoice", false).unwrap();
+//! let verified = verify(&signed, None).unwrap();
+//! assert_eq!(b"invoice".to_vec(), verified.content);
+//! ```
+
+use std::time::SystemTime;
+
+use cms::cert::x509::attr::Attribute;
+use cms::cert::x509::ext::pkix::SubjectKeyIdentifier;
+use cms::cert::x509::spki::AlgorithmIdentifierOwned;
+use cms::cert::x509::time::Time;
+use cms::cert::x509::Certificate;
+use cms::cert::{CertificateChoices, IssuerAndSerialNumber};
+use cms::content_info::{CmsVersion, ContentInfo};
+use cms::signed_data::{
+    CertificateSet, EncapsulatedContentInfo, SignedAttributes, SignedData, SignerIdentifier,
+    SignerInfo, SignerInfos,
+};
+use gm_sm3::{sm3_hash, OID_SM3};
+use pkcs8::der::asn1::{GeneralizedTime, OctetString, SetOfVec, UtcTime};
+use pkcs8::der::{Any, Decode, Encode};
+use pkcs8::ObjectIdentifier;
+use rand::{CryptoRng, RngCore};
+
+use crate::error::{Sm2Error, Sm2Result};
+use crate::key::{Sm2PrivateKey, Sm2PublicKey};
+use crate::signature::Sm2Signature;
+use crate::{OID_SM2_CMS_1, OID_SM2_CMS_DATA, OID_SM2_CMS_SIGNED, OID_SM2_PKCS8, OID_SM2_SM3};
+
+/// content-type attribute, refer to RFC 5652
+const OID_CONTENT_TYPE: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.3");
+/// message-digest attribute, refer to RFC 5652
+const OID_MESSAGE_DIGEST: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4");
+/// signing-time attribute, refer to RFC 5652
+const OID_SIGNING_TIME: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.5");
+/// PKCS #7 data content type, refer to RFC 5652
+const OID_PKCS7_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.1");
+/// PKCS #7 signedData content type, refer to RFC 5652
+const OID_PKCS7_SIGNED: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+
+/// A private key together with the certificate of its public key.
+pub struct Signer<'a> {
+    key: &'a Sm2PrivateKey,
+    certificate: Certificate,
+}
+
+impl<'a> Signer<'a> {
+    /// Fails with [`Sm2Error::InvalidPublic`] if the certificate is not issued
+    /// for `key`.
+    pub fn new(key: &'a Sm2PrivateKey, certificate: Certificate) -> Sm2Result<Signer<'a>> {
+        let pk = certificate_public_key(&certificate)?;
+        if pk.to_bytes(false) != key.to_public_key().to_bytes(false) {
+            return Err(Sm2Error::InvalidPublic);
+        }
+        Ok(Signer { key, certificate })
+    }
+
+    /// Like [`Signer::new`] with a DER encoded certificate.
+    pub fn from_der(key: &'a Sm2PrivateKey, certificate: &[u8]) -> Sm2Result<Signer<'a>> {
+        let certificate = Certificate::from_der(certificate).map_err(|_| Sm2Error::InvalidDer)?;
+        Signer::new(key, certificate)
+    }
+}
+
+/// A signer whose signature has been verified.
+#[derive(Debug, Clone)]
+pub struct VerifiedSigner {
+    pub certificate: Certificate,
+    /// The signing-time attribute, as claimed by the signer.
+    pub signing_time: Option<SystemTime>,
+}
+
+/// The result of [`verify`].
+#[derive(Debug, Clone)]
+pub struct SignedContent {
+    pub content: Vec<u8>,
+    pub signers: Vec<VerifiedSigner>,
+}
+
+/// Sign `content` by every signer at the current time and return the DER
+/// `ContentInfo`. A `detached` signature leaves the content out of the message.
+pub fn sign(signers: &[Signer], content: &[u8], detached: bool) -> Sm2Result<Vec<u8>> {
+    sign_with_rng(
+        &mut rand::thread_rng(),
+        signers,
+        content,
+        detached,
+        Some(SystemTime::now()),
+    )
+}
+
+/// Like [`sign`] with the signing-time attribute set to `signing_time`, or
+/// left out if it is `None`.
+pub fn sign_with_rng<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    signers: &[Signer],
+    content: &[u8],
+    detached: bool,
+    signing_time: Option<SystemTime>,
+) -> Sm2Result<Vec<u8>> {
+    if signers.is_empty() {
+        return Err(Sm2Error::NoSigner);
+    }
+    let digest = sm3_hash(content);
+    let mut attrs = vec![
+        attribute(OID_CONTENT_TYPE, &OID_SM2_CMS_DATA)?,
+        attribute(OID_MESSAGE_DIGEST, &octet_string(&digest)?)?,
+    ];
+    if let Some(time) = signing_time {
+        // UTCTime up to 2049, GeneralizedTime afterwards
+        let time = match UtcTime::from_system_time(time) {
+            Ok(utc) => Time::UtcTime(utc),
+            Err(_) => Time::GeneralTime(
+                GeneralizedTime::from_system_time(time).map_err(|_| Sm2Error::InvalidDer)?,
+            ),
+        };
+        attrs.push(attribute(OID_SIGNING_TIME, &time)?);
+    }
+    let attrs: SignedAttributes = SetOfVec::try_from(attrs).map_err(|_| Sm2Error::InvalidDer)?;
+    let attrs_der = attrs.to_der().map_err(|_| Sm2Error::InvalidDer)?;
+
+    let mut infos = vec![];
+    let mut certificates: Vec<Certificate> = vec![];
+    for signer in signers {
+        let sig = signer.key.sign_with_rng(rng, None, &attrs_der)?;
+        let tbs = &signer.certificate.tbs_certificate;
+        infos.push(SignerInfo {
+            version: CmsVersion::V1,
+            sid: SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+                issuer: tbs.issuer.clone(),
+                serial_number: tbs.serial_number.clone(),
+            }),
+            digest_alg: sm3_algorithm(),
+            signed_attrs: Some(attrs.clone()),
+            signature_algorithm: AlgorithmIdentifierOwned {
+                oid: OID_SM2_SM3,
+                parameters: None,
+            },
+            signature: octet_string(&Sm2Signature::from_bytes(&sig)?.to_der())?,
+            unsigned_attrs: None,
+        });
+        if !certificates.contains(&signer.certificate) {
+            certificates.push(signer.certificate.clone());
+        }
+    }
+
+    let econtent = if detached {
+        None
+    } else {
+        Some(Any::encode_from(&octet_string(content)?).map_err(|_| Sm2Error::InvalidDer)?)
+    };
+    let certificates = certificates
+        .into_iter()
+        .map(CertificateChoices::Certificate)
+        .collect::<Vec<_>>();
+    let signed = SignedData {
+        version: CmsVersion::V1,
+        digest_algorithms: SetOfVec::try_from(vec![sm3_algorithm()])
+            .map_err(|_| Sm2Error::InvalidDer)?,
+        encap_content_info: EncapsulatedContentInfo {
+            econtent_type: OID_SM2_CMS_DATA,
+            econtent,
+        },
+        certificates: Some(CertificateSet(
+            SetOfVec::try_from(certificates).map_err(|_| Sm2Error::InvalidDer)?,
+        )),
+        crls: None,
+        signer_infos: SignerInfos(SetOfVec::try_from(infos).map_err(|_| Sm2Error::InvalidDer)?),
+    };
+    ContentInfo {
+        content_type: OID_SM2_CMS_SIGNED,
+        content: Any::encode_from(&signed).map_err(|_| Sm2Error::InvalidDer)?,
+    }
+    .to_der()
+    .map_err(|_| Sm2Error::InvalidDer)
+}
+
+/// Verify all signatures of a DER `SignedData` and return the content.
+///
+/// `detached` supplies the content of a detached signature and is ignored if
+/// the message carries the content itself. Each signer's certificate must be
+/// embedded in the message; [`Sm2Error::NoSigner`] is returned otherwise, or if
+/// there is no signer at all.
+pub fn verify(signed: &[u8], detached: Option<&[u8]>) -> Sm2Result<SignedContent> {
+    let content_info = ContentInfo::from_der(signed).map_err(|_| Sm2Error::InvalidDer)?;
+    if content_info.content_type != OID_SM2_CMS_SIGNED
+        && content_info.content_type != OID_PKCS7_SIGNED
+    {
+        return Err(Sm2Error::UnsupportedAlgorithm);
+    }
+    let signed: SignedData = content_info
+        .content
+        .decode_as()
+        .map_err(|_| Sm2Error::InvalidDer)?;
+
+    let encap = &signed.encap_content_info;
+    if encap.econtent_type != OID_SM2_CMS_DATA && encap.econtent_type != OID_PKCS7_DATA {
+        return Err(Sm2Error::UnsupportedAlgorithm);
+    }
+    let content = match (&encap.econtent, detached) {
+        (Some(econtent), _) => econtent
+            .decode_as::<OctetString>()
+            .map_err(|_| Sm2Error::InvalidDer)?
+            .into_bytes(),
+        (None, Some(detached)) => detached.to_vec(),
+        (None, None) => return Err(Sm2Error::ContentMissing),
+    };
+    let certificates: Vec<&Certificate> = signed
+        .certificates
+        .iter()
+        .flat_map(|set| set.0.iter())
+        .filter_map(|choice| match choice {
+            CertificateChoices::Certificate(cert) => Some(cert),
+            _ => None,
+        })
+        .collect();
+
+    if signed.signer_infos.0.is_empty() {
+        return Err(Sm2Error::NoSigner);
+    }
+    let mut signers = vec![];
+    for info in signed.signer_infos.0.iter() {
+        let certificate = certificates
+            .iter()
+            .find(|cert| is_signer_certificate(cert, &info.sid))
+            .ok_or(Sm2Error::NoSigner)?;
+        signers.push(verify_signer(
+            info,
+            certificate,
+            encap.econtent_type,
+            &content,
+        )?);
+    }
+    Ok(SignedContent { content, signers })
+}
+
+fn verify_signer(
+    info: &SignerInfo,
+    certificate: &Certificate,
+    content_type: ObjectIdentifier,
+    content: &[u8],
+) -> Sm2Result<VerifiedSigner> {
+    if info.digest_alg.oid != OID_SM3 {
+        return Err(Sm2Error::UnsupportedAlgorithm);
+    }
+    let sig_oid = info.signature_algorithm.oid;
+    if sig_oid != OID_SM2_SM3 && sig_oid != OID_SM2_CMS_1 && sig_oid != OID_SM2_PKCS8 {
+        return Err(Sm2Error::UnsupportedAlgorithm);
+    }
+
+    let mut signing_time = None;
+    let signed_msg = match &info.signed_attrs {
+        Some(attrs) => {
+            let value = |oid| {
+                attrs
+                    .iter()
+                    .find(|attr| attr.oid == oid)
+                    .and_then(|attr| attr.values.get(0))
+            };
+            let attr_type = value(OID_CONTENT_TYPE)
+                .and_then(|v| v.decode_as::<ObjectIdentifier>().ok())
+                .ok_or(Sm2Error::InvalidDer)?;
+            let attr_digest = value(OID_MESSAGE_DIGEST)
+                .and_then(|v| v.decode_as::<OctetString>().ok())
+                .ok_or(Sm2Error::InvalidDer)?;
+            if attr_type != content_type {
+                return Err(Sm2Error::InvalidDer);
+            }
+            if attr_digest.as_bytes() != sm3_hash(content) {
+                return Err(Sm2Error::InvalidDigest);
+            }
+            if let Some(time) = value(OID_SIGNING_TIME) {
+                let time = time
+                    .to_der()
+                    .and_then(|der| Time::from_der(&der))
+                    .map_err(|_| Sm2Error::InvalidDer)?;
+                signing_time = Some(time.to_date_time().to_system_time());
+            }
+            attrs.to_der().map_err(|_| Sm2Error::InvalidDer)?
+        }
+        None => content.to_vec(),
+    };
+    // GmSSL and `sign` use the default ID, OpenSSL 3 `cms` an empty one
+    let pk = certificate_public_key(certificate)?;
+    let sig = info.signature.as_bytes();
+    pk.verify_der(None, &signed_msg, sig)
+        .or_else(|_| pk.verify_der(Some(b""), &signed_msg, sig))?;
+    Ok(VerifiedSigner {
+        certificate: certificate.clone(),
+        signing_time,
+    })
+}
+
+fn is_signer_certificate(certificate: &Certificate, sid: &SignerIdentifier) -> bool {
+    let tbs = &certificate.tbs_certificate;
+    match sid {
+        SignerIdentifier::IssuerAndSerialNumber(id) => {
+            tbs.issuer == id.issuer && tbs.serial_number == id.serial_number
+        }
+        SignerIdentifier::SubjectKeyIdentifier(ski) => {
+            matches!(tbs.get::<SubjectKeyIdentifier>(), Ok(Some((_, ref own))) if own == ski)
+        }
+    }
+}
+
+fn certificate_public_key(certificate: &Certificate) -> Sm2Result<Sm2PublicKey> {
+    let spki = &certificate.tbs_certificate.subject_public_key_info;
+    Sm2PublicKey::new(
+        spki.subject_public_key
+            .as_bytes()
+            .ok_or(Sm2Error::InvalidDer)?,
+    )
+}
+
+fn sm3_algorithm() -> AlgorithmIdentifierOwned {
+    AlgorithmIdentifierOwned {
+        oid: OID_SM3,
+        parameters: None,
+    }
+}
+
+fn octet_string(bytes: &[u8]) -> Sm2Result<OctetString> {
+    OctetString::new(bytes).map_err(|_| Sm2Error::InvalidDer)
+}
+
+fn attribute<T: Encode>(oid: ObjectIdentifier, value: &T) -> Sm2Result<Attribute> {
+    let value = value
+        .to_der()
+        .and_then(|der| Any::from_der(&der))
+        .map_err(|_| Sm2Error::InvalidDer)?;
+    Ok(Attribute {
+        oid,
+        values: SetOfVec::try_from(vec![value]).map_err(|_| Sm2Error::InvalidDer)?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use std::time::{Duration, SystemTime};
+
+    use cms::cert::x509::certificate::{TbsCertificate, Version};
+    use cms::cert::x509::name::Name;
+    use cms::cert::x509::serial_number::SerialNumber;
+    use cms::cert::x509::spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned};
+    use cms::cert::x509::time::{Time, Validity};
+    use cms::cert::x509::Certificate;
+    use pkcs8::der::asn1::{BitString, UtcTime};
+    use pkcs8::der::{Any, Encode};
+
+    use cms::cert::x509::ext::pkix::SubjectKeyIdentifier;
+    use cms::content_info::ContentInfo;
+    use cms::signed_data::{SignedData, SignerIdentifier};
+    use pkcs8::der::Decode;
+
+    use crate::error::Sm2Error;
+    use crate::key::{gen_keypair, Sm2PrivateKey};
+    use crate::signed::{certificate_public_key, sign, sign_with_rng, verify, Signer};
+    use crate::{ALGORITHM_OID, OID_SM2_PKCS8, OID_SM2_SM3};
+
+    // self-signed SM2 certificate made by OpenSSL 3 for this key
+    const SIGNER_KEY: &str = "17fa99bb179f3a16797b4b167706b6c5a2fa84ee501ecff83140e231852b78ce";
+    const SIGNER_CERT: &[u8] = include_bytes!("../tests/data/sm2_signer.der");
+    // `sign_with_rng` output for SIGNER_CERT over b"e-invoice 0001", signing
+    // time 1_790_000_000; the signed attributes verify with
+    // `openssl pkeyutl -verify -rawin -digest sm3 -pkeyopt distid:1234567812345678`
+    const SIGNED_ATTACHED: &[u8] = include_bytes!("../tests/data/sm2_signed_attached.der");
+    const SIGNED_DETACHED: &[u8] = include_bytes!("../tests/data/sm2_signed_detached.der");
+    // `openssl cms -sign -md sm3 -binary [-nodetach]` of b"e-invoice 0001" with
+    // SIGNER_CERT, OpenSSL 3.5
+    const OPENSSL_ATTACHED: &[u8] = include_bytes!("../tests/data/openssl_signed_attached.der");
+    const OPENSSL_DETACHED: &[u8] = include_bytes!("../tests/data/openssl_signed_detached.der");
+
+    fn self_signed(sk: &Sm2PrivateKey, name: &str, serial: u8) -> Certificate {
+        let name = Name::from_str(name).unwrap();
+        let time =
+            |secs| Time::UtcTime(UtcTime::from_unix_duration(Duration::from_secs(secs)).unwrap());
+        let tbs_certificate = TbsCertificate {
+            version: Version::V3,
+            serial_number: SerialNumber::new(&[serial]).unwrap(),
+            signature: AlgorithmIdentifierOwned {
+                oid: OID_SM2_SM3,
+                parameters: None,
+            },
+            issuer: name.clone(),
+            validity: Validity {
+                not_before: time(1_600_000_000),
+                not_after: time(2_400_000_000),
+            },
+            subject: name,
+            subject_public_key_info: SubjectPublicKeyInfoOwned {
+                algorithm: AlgorithmIdentifierOwned {
+                    oid: ALGORITHM_OID,
+                    parameters: Some(Any::from(OID_SM2_PKCS8)),
+                },
+                subject_public_key: BitString::from_bytes(&sk.to_public_key().to_bytes(false))
+                    .unwrap(),
+            },
+            issuer_unique_id: None,
+            subject_unique_id: None,
+            extensions: None,
+        };
+        let signature = sk
+            .sign_der(None, &tbs_certificate.to_der().unwrap())
+            .unwrap();
+        Certificate {
+            tbs_certificate,
+            signature_algorithm: AlgorithmIdentifierOwned {
+                oid: OID_SM2_SM3,
+                parameters: None,
+            },
+            signature: BitString::from_bytes(&signature).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_signed_data() {
+        let (_, sk_a) = gen_keypair().unwrap();
+        let (_, sk_b) = gen_keypair().unwrap();
+        let cert_a = self_signed(&sk_a, "CN=alice,O=Example", 1);
+        let cert_b = self_signed(&sk_b, "CN=bob,O=Example", 2);
+        let signers = [
+            Signer::new(&sk_a, cert_a.clone()).unwrap(),
+            Signer::from_der(&sk_b, &cert_b.to_der().unwrap()).unwrap(),
+        ];
+        let msg = b"e-invoice 0001";
+
+        let attached = sign(&signers, msg, false).unwrap();
+        let verified = verify(&attached, None).unwrap();
+        assert_eq!(msg.to_vec(), verified.content);
+        assert_eq!(2, verified.signers.len());
+        for signer in verified.signers.iter() {
+            assert!(signer.certificate == cert_a || signer.certificate == cert_b);
+            let elapsed = SystemTime::now()
+                .duration_since(signer.signing_time.unwrap())
+                .unwrap();
+            assert!(elapsed < Duration::from_secs(60));
+        }
+        // the attached content wins over a detached one
+        assert_eq!(
+            msg.to_vec(),
+            verify(&attached, Some(b"other")).unwrap().content
+        );
+
+        let detached = sign(&signers, msg, true).unwrap();
+        assert!(detached.len() < attached.len());
+        assert_eq!(msg.to_vec(), verify(&detached, Some(msg)).unwrap().content);
+        assert_eq!(
+            Sm2Error::InvalidDigest,
+            verify(&detached, Some(b"e-invoice 0002")).unwrap_err()
+        );
+        assert_eq!(
+            Sm2Error::ContentMissing,
+            verify(&detached, None).unwrap_err()
+        );
+
+        let untimed =
+            sign_with_rng(&mut rand::thread_rng(), &signers[..1], msg, false, None).unwrap();
+        let verified = verify(&untimed, None).unwrap();
+        assert_eq!(1, verified.signers.len());
+        assert_eq!(None, verified.signers[0].signing_time);
+    }
+
+    #[test]
+    fn test_signed_data_invalid() {
+        let (_, sk) = gen_keypair().unwrap();
+        let (_, other) = gen_keypair().unwrap();
+        let cert = self_signed(&sk, "CN=alice", 1);
+        assert_eq!(
+            Sm2Error::InvalidPublic,
+            Signer::new(&other, cert.clone()).err().unwrap()
+        );
+        assert_eq!(Sm2Error::NoSigner, sign(&[], b"msg", false).unwrap_err());
+
+        let signed = sign(&[Signer::new(&sk, cert).unwrap()], b"msg", false).unwrap();
+        // flip a byte of the attached content
+        let pos = signed.windows(3).position(|w| w == b"msg").unwrap();
+        let mut tampered = signed.clone();
+        tampered[pos] ^= 0x01;
+        assert_eq!(
+            Sm2Error::InvalidDigest,
+            verify(&tampered, None).unwrap_err()
+        );
+
+        assert_eq!(
+            Sm2Error::InvalidDer,
+            verify(&signed[..signed.len() - 1], None).unwrap_err()
+        );
+        assert!(verify(&[], None).is_err());
+    }
+
+    #[test]
+    fn test_signed_data_fixtures() {
+        let cert = Certificate::from_der(SIGNER_CERT).unwrap();
+        // the certificate signature is made by OpenSSL
+        certificate_public_key(&cert)
+            .unwrap()
+            .verify_der(
+                None,
+                &cert.tbs_certificate.to_der().unwrap(),
+                cert.signature.raw_bytes(),
+            )
+            .unwrap();
+        let sk = Sm2PrivateKey::from_hex_string(SIGNER_KEY).unwrap();
+        Signer::from_der(&sk, SIGNER_CERT).unwrap();
+
+        let msg = b"e-invoice 0001";
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_790_000_000);
+        for (signed, detached) in [(SIGNED_ATTACHED, None), (SIGNED_DETACHED, Some(&msg[..]))] {
+            let verified = verify(signed, detached).unwrap();
+            assert_eq!(msg.to_vec(), verified.content);
+            assert_eq!(1, verified.signers.len());
+            assert_eq!(cert, verified.signers[0].certificate);
+            assert_eq!(Some(time), verified.signers[0].signing_time);
+        }
+        assert_eq!(
+            Sm2Error::ContentMissing,
+            verify(SIGNED_DETACHED, None).unwrap_err()
+        );
+        assert_eq!(
+            Sm2Error::InvalidDigest,
+            verify(SIGNED_DETACHED, Some(b"e-invoice 0002")).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_signed_data_openssl() {
+        let cert = Certificate::from_der(SIGNER_CERT).unwrap();
+        let msg = b"e-invoice 0001";
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_792_201_729);
+        for (signed, detached) in [(OPENSSL_ATTACHED, None), (OPENSSL_DETACHED, Some(&msg[..]))] {
+            let verified = verify(signed, detached).unwrap();
+            assert_eq!(msg.to_vec(), verified.content);
+            assert_eq!(1, verified.signers.len());
+            assert_eq!(cert, verified.signers[0].certificate);
+            assert_eq!(Some(time), verified.signers[0].signing_time);
+        }
+        assert_eq!(
+            Sm2Error::InvalidDigest,
+            verify(OPENSSL_DETACHED, Some(b"e-invoice 0002")).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_signed_data_subject_key_identifier() {
+        let cert = Certificate::from_der(SIGNER_CERT).unwrap();
+        let (_, ski) = cert
+            .tbs_certificate
+            .get::<SubjectKeyIdentifier>()
+            .unwrap()
+            .unwrap();
+        let mut content_info = ContentInfo::from_der(SIGNED_ATTACHED).unwrap();
+        let mut signed: SignedData = content_info.content.decode_as().unwrap();
+        let mut infos = signed.signer_infos.0.into_vec();
+        infos[0].sid = SignerIdentifier::SubjectKeyIdentifier(ski);
+        signed.signer_infos.0 = infos.try_into().unwrap();
+        content_info.content = Any::encode_from(&signed).unwrap();
+
+        let verified = verify(&content_info.to_der().unwrap(), None).unwrap();
+        assert_eq!(cert, verified.signers[0].certificate);
+    }
+}
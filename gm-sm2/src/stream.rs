@@ -0,0 +1,367 @@
+//! Streaming SM2 encryption in the `C1 || C2 || C3` layout.
+//!
+//! [`Sm2StreamEncryptor`] writes `C1` as soon as it is created, masks each
+//! chunk written to it with the next bytes of the KDF stream and appends `C3`
+//! when finished. [`Sm2StreamDecryptor`] reads `C1`, unmasks `C2` as it is read
+//! and checks `C3` at the end of the input. Memory use does not depend on the
+//! message length.
+//!
+//! The output is byte-for-byte what [`Sm2PublicKey::encrypt`] produces with
+//! [`Sm2Model::C1C2C3`], and either side can be mixed with the one-shot API.
+//!
+//! Decrypted data is released before `C3` can be checked. It must not be
+//! trusted until [`Read::read`] has returned `Ok(0)`; a modified ciphertext is
+//! only reported as an error at the end of the stream.
+//!
+//! # Example
+//! ```rust
+//! use std::io::{Read, Write};
+//! use gm_sm2::key::gen_keypair;
+//! use gm_sm2::stream::{Sm2StreamDecryptor, Sm2StreamEncryptor};
+//! fn main(){
+//!     let (pk, sk) = gen_keypair().unwrap();
+//!     let mut encryptor = Sm2StreamEncryptor::new(&pk, vec![], false).unwrap();
+//!     encryptor.write_all(b"a large ").unwrap();
+//!     encryptor.write_all(b"backup").unwrap();
+//!     let ciphertext = encryptor.finish().unwrap();
+//!
+//!     let mut decryptor = Sm2StreamDecryptor::new(&sk, &ciphertext[..], false).unwrap();
+//!     let mut plain = vec![];
+//!     decryptor.read_to_end(&mut plain).unwrap();
+//!     assert_eq!(b"a large backup".to_vec(), plain);
+//! }
+//! ```
+//!
+//! [`Sm2PublicKey::encrypt`]: crate::key::Sm2PublicKey::encrypt
+//! [`Sm2Model::C1C2C3`]: crate::key::Sm2Model::C1C2C3
+
+use std::io::{self, Read, Write};
+
+use gm_sm3::{Sm3, Sm3KdfReader};
+use rand::{CryptoRng, RngCore};
+use zeroize::Zeroizing;
+
+use crate::error::{Sm2Error, Sm2Result};
+use crate::fields::fp64::{fp_from_mont, random_u256_with_rng};
+use crate::fields::FieldModOperation;
+use crate::key::{read_c1, Sm2PrivateKey, Sm2PublicKey};
+use crate::p256_ecc::{g_mul_ct, Point};
+
+/// Key stream and `C3` state shared by both directions, derived from
+/// `(x2, y2) = [k]P = [d]C1`.
+struct StreamState {
+    kdf: Sm3KdfReader,
+    hasher: Sm3,
+    y2: Zeroizing<Vec<u8>>,
+    // the standard rejects an all-zero key stream; an empty one masks nothing
+    used: bool,
+    nonzero: bool,
+}
+
+impl StreamState {
+    fn new(c2_point: &Point) -> StreamState {
+        let x2 = Zeroizing::new(fp_from_mont(&c2_point.x).to_byte_be());
+        let y2 = Zeroizing::new(fp_from_mont(&c2_point.y).to_byte_be());
+        let mut z: Zeroizing<Vec<u8>> = Zeroizing::new(vec![]);
+        z.extend_from_slice(&x2);
+        z.extend_from_slice(&y2);
+        let mut hasher = Sm3::new();
+        hasher.update(&x2);
+        StreamState {
+            kdf: Sm3KdfReader::new(&z),
+            hasher,
+            y2,
+            used: false,
+            nonzero: false,
+        }
+    }
+
+    /// XOR `data` with the next bytes of key stream in place.
+    fn apply(&mut self, data: &mut [u8]) -> io::Result<()> {
+        let mut t = Zeroizing::new(vec![0u8; data.len()]);
+        self.kdf
+            .read(&mut t)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.used |= !data.is_empty();
+        for (d, k) in data.iter_mut().zip(t.iter()) {
+            self.nonzero |= *k != 0;
+            *d ^= k;
+        }
+        Ok(())
+    }
+
+    fn c3(self) -> Sm2Result<[u8; 32]> {
+        if self.used && !self.nonzero {
+            return Err(Sm2Error::ZeroData);
+        }
+        let mut hasher = self.hasher;
+        hasher.update(&self.y2);
+        Ok(hasher.finalize())
+    }
+}
+
+/// Encrypts everything written to it into `inner` as `C1 || C2 || C3`.
+///
+/// [`Sm2StreamEncryptor::finish`] must be called to append `C3`. An error from
+/// `inner` leaves the ciphertext unusable.
+pub struct Sm2StreamEncryptor<W: Write> {
+    inner: W,
+    state: StreamState,
+}
+
+impl<W: Write> Sm2StreamEncryptor<W> {
+    /// Start a ciphertext for `pk` and write `C1` to `inner`.
+    pub fn new(pk: &Sm2PublicKey, inner: W, compressed: bool) -> io::Result<Sm2StreamEncryptor<W>> {
+        Self::new_with_rng(&mut rand::thread_rng(), pk, inner, compressed)
+    }
+
+    /// Like [`Sm2StreamEncryptor::new`], drawing the ephemeral key from `rng`.
+    pub fn new_with_rng<R: CryptoRng + RngCore>(
+        rng: &mut R,
+        pk: &Sm2PublicKey,
+        mut inner: W,
+        compressed: bool,
+    ) -> io::Result<Sm2StreamEncryptor<W>> {
+        if pk.value().is_zero() {
+            return Err(Sm2Error::ZeroPoint.into());
+        }
+        let k = Zeroizing::new(random_u256_with_rng(rng));
+        let c1 = g_mul_ct(&k).to_affine_point();
+        let c2_point = pk.value().scalar_mul_ct(&k).to_affine_point();
+        inner.write_all(&c1.to_byte_be(compressed))?;
+        Ok(Sm2StreamEncryptor {
+            inner,
+            state: StreamState::new(&c2_point),
+        })
+    }
+
+    /// Append `C3` and return the inner writer.
+    ///
+    /// Fails with [`Sm2Error::ZeroData`] in the negligible case of an all-zero
+    /// key stream, in which case the whole encryption has to be started again.
+    /// Nothing written gives an empty `C2`, as with [`Sm2PublicKey::encrypt`].
+    ///
+    /// [`Sm2PublicKey::encrypt`]: crate::key::Sm2PublicKey::encrypt
+    pub fn finish(mut self) -> io::Result<W> {
+        let c3 = self.state.c3()?;
+        self.inner.write_all(&c3)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for Sm2StreamEncryptor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.state.hasher.update(buf);
+        let mut c2 = buf.to_vec();
+        self.state.apply(&mut c2)?;
+        self.inner.write_all(&c2)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts a `C1 || C2 || C3` ciphertext read from `inner`.
+///
+/// The last 32 bytes read are held back as the candidate `C3`, so `C2` is
+/// released with a 32-byte delay. At the end of `inner` the hash is checked
+/// and a mismatch is returned as an [`io::ErrorKind::InvalidData`] error
+/// wrapping [`Sm2Error::HashNotEqual`].
+pub struct Sm2StreamDecryptor<R: Read> {
+    inner: R,
+    state: Option<StreamState>,
+    buf: Vec<u8>,
+    // outcome of the C3 check, repeated on every read after the end
+    result: Option<Sm2Result<()>>,
+}
+
+impl<R: Read> Sm2StreamDecryptor<R> {
+    /// Read and check `C1` from `inner`.
+    pub fn new(
+        sk: &Sm2PrivateKey,
+        mut inner: R,
+        compressed: bool,
+    ) -> io::Result<Sm2StreamDecryptor<R>> {
+        let mut c1_bytes = vec![0u8; if compressed { 33 } else { 65 }];
+        inner.read_exact(&mut c1_bytes)?;
+        let c1 = read_c1(&c1_bytes, compressed)?;
+        let c2_point = c1.scalar_mul_ct(&sk.d).to_affine_point();
+        Ok(Sm2StreamDecryptor {
+            inner,
+            state: Some(StreamState::new(&c2_point)),
+            buf: vec![],
+            result: None,
+        })
+    }
+
+    /// Return the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// At the end of the input, what is left must be `C3`.
+    fn check_c3(&mut self) -> Sm2Result<()> {
+        let state = self.state.take().ok_or(Sm2Error::InvalidCiphertext)?;
+        if self.buf.len() != 32 {
+            return Err(Sm2Error::InvalidCiphertext);
+        }
+        let c3 = state.c3()?;
+        if c3 != self.buf[..] {
+            return Err(Sm2Error::HashNotEqual);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Sm2StreamDecryptor<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if let Some(result) = self.result {
+            return Ok(result.map(|_| 0)?);
+        }
+        if out.is_empty() {
+            return Ok(0);
+        }
+        while self.buf.len() <= 32 {
+            let filled = self.buf.len();
+            self.buf.resize(filled + out.len().max(32), 0);
+            let n = match self.inner.read(&mut self.buf[filled..]) {
+                Ok(n) => n,
+                Err(e) => {
+                    self.buf.truncate(filled);
+                    return Err(e);
+                }
+            };
+            self.buf.truncate(filled + n);
+            if n == 0 {
+                let result = self.check_c3();
+                self.result = Some(result);
+                return Ok(result.map(|_| 0)?);
+            }
+        }
+
+        let state = self.state.as_mut().unwrap();
+        let n = (self.buf.len() - 32).min(out.len());
+        out[..n].copy_from_slice(&self.buf[..n]);
+        self.buf.drain(..n);
+        state.apply(&mut out[..n])?;
+        state.hasher.update(&out[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Read, Write};
+
+    use crate::error::Sm2Error;
+    use crate::key::{gen_keypair, Sm2Model};
+    use crate::stream::{Sm2StreamDecryptor, Sm2StreamEncryptor};
+
+    /// A reader that hands out at most `chunk` bytes per call.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            let n = self.chunk.min(out.len()).min(self.data.len());
+            out[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    fn sm2_error(e: &io::Error) -> Sm2Error {
+        assert_eq!(io::ErrorKind::InvalidData, e.kind());
+        *e.get_ref().unwrap().downcast_ref::<Sm2Error>().unwrap()
+    }
+
+    #[test]
+    fn test_stream_matches_one_shot() {
+        let (pk, sk) = gen_keypair().unwrap();
+        let msg: Vec<u8> = (0..5000u32).map(|i| (i * 7) as u8).collect();
+        for compressed in [false, true] {
+            let mut encryptor = Sm2StreamEncryptor::new(&pk, vec![], compressed).unwrap();
+            for chunk in msg.chunks(333) {
+                encryptor.write_all(chunk).unwrap();
+            }
+            let ciphertext = encryptor.finish().unwrap();
+            assert_eq!(
+                msg,
+                sk.decrypt(&ciphertext, compressed, Sm2Model::C1C2C3)
+                    .unwrap()
+            );
+
+            let ciphertext = pk.encrypt(&msg, compressed, Sm2Model::C1C2C3).unwrap();
+            for chunk in [1, 31, 32, 33, 4096] {
+                let reader = Trickle {
+                    data: &ciphertext,
+                    chunk,
+                };
+                let mut decryptor = Sm2StreamDecryptor::new(&sk, reader, compressed).unwrap();
+                let mut plain = vec![];
+                decryptor.read_to_end(&mut plain).unwrap();
+                assert_eq!(msg, plain);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream_copy() {
+        let (pk, sk) = gen_keypair().unwrap();
+        let msg = vec![0x5au8; 1 << 20];
+        let mut encryptor = Sm2StreamEncryptor::new(&pk, vec![], false).unwrap();
+        io::copy(&mut &msg[..], &mut encryptor).unwrap();
+        let ciphertext = encryptor.finish().unwrap();
+        assert_eq!(65 + msg.len() + 32, ciphertext.len());
+
+        let mut decryptor = Sm2StreamDecryptor::new(&sk, &ciphertext[..], false).unwrap();
+        let mut plain = vec![];
+        io::copy(&mut decryptor, &mut plain).unwrap();
+        assert_eq!(msg, plain);
+    }
+
+    #[test]
+    fn test_stream_rejects_tampering() {
+        let (pk, sk) = gen_keypair().unwrap();
+        let ciphertext = pk.encrypt(b"stream me", false, Sm2Model::C1C2C3).unwrap();
+
+        let mut tampered = ciphertext.clone();
+        tampered[70] ^= 1;
+        let mut decryptor = Sm2StreamDecryptor::new(&sk, &tampered[..], false).unwrap();
+        let err = decryptor.read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(Sm2Error::HashNotEqual, sm2_error(&err));
+        // the failure sticks, it never turns into a clean end of stream
+        let err = decryptor.read(&mut [0u8; 16]).unwrap_err();
+        assert_eq!(Sm2Error::HashNotEqual, sm2_error(&err));
+
+        let truncated = &ciphertext[..ciphertext.len() - 1];
+        let mut decryptor = Sm2StreamDecryptor::new(&sk, truncated, false).unwrap();
+        assert!(decryptor.read_to_end(&mut vec![]).is_err());
+
+        let mut decryptor = Sm2StreamDecryptor::new(&sk, &ciphertext[..65 + 31], false).unwrap();
+        let err = decryptor.read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(Sm2Error::InvalidCiphertext, sm2_error(&err));
+
+        assert!(Sm2StreamDecryptor::new(&sk, &ciphertext[..64], false).is_err());
+        assert!(Sm2StreamDecryptor::new(&sk, &ciphertext[..], true).is_err());
+
+        // an empty message round-trips with the one-shot API
+        let encryptor = Sm2StreamEncryptor::new(&pk, vec![], false).unwrap();
+        let ciphertext = encryptor.finish().unwrap();
+        assert_eq!(65 + 32, ciphertext.len());
+        assert!(sk
+            .decrypt(&ciphertext, false, Sm2Model::C1C2C3)
+            .unwrap()
+            .is_empty());
+        let ciphertext = pk.encrypt(b"", false, Sm2Model::C1C2C3).unwrap();
+        let mut decryptor = Sm2StreamDecryptor::new(&sk, &ciphertext[..], false).unwrap();
+        let mut plain = vec![];
+        decryptor.read_to_end(&mut plain).unwrap();
+        assert!(plain.is_empty());
+    }
+}
@@ -0,0 +1,1151 @@
+//! Threshold SM2: Feldman verifiable secret sharing, distributed key generation
+//! and t-of-n signing.
+//!
+//! SM2 signs with `s = (1 + d)^-1 * (k + r) - r`. The parties therefore share
+//! `w = (1 + d)^-1` with Shamir polynomials of degree `t - 1` over the scalar
+//! field, and the signature becomes linear in the shares:
+//!
+//! * key generation: every party deals random sharings of `w` and of a mask `a`
+//!   (Feldman commitments over `G`), plus a sharing of zero of degree `2t - 2`.
+//!   The masked product `v = w * a` is opened once, and with `A = [a]G` the
+//!   parties get `Q = [v^-1]A = [w^-1]G` and the public key `P = Q - G`
+//!   without anybody learning `w`. Opening the product needs `2t - 1` parties,
+//!   so key generation requires `n >= 2t - 1`. The product shares themselves
+//!   cannot be checked against commitments, so each party also publishes
+//!   `[w_j]A` with a Chaum-Pedersen proof that `w_j` is the share behind the
+//!   Feldman commitments; these interpolate to `[w * a]G`, which the opened
+//!   `v` has to match;
+//! * signing: the signers deal a random `u` with Feldman commitments over `Q`,
+//!   so `K = [u]Q = [k]G` for the nonce `k = u * w^-1`. With `r = e + x(K)`,
+//!   each signer publishes `s_j = u_j + r * w_j`, any `t` of them interpolate
+//!   to `u + r * w` and `s = u + r * w - r` is the ordinary SM2 signature.
+//!
+//! Shares that do not match their dealer's commitments are rejected with
+//! [`Sm2Error::InvalidShare`]. So is a wrong product share in
+//! [`DkgParty::finish`], although it cannot be attributed to its sender: key
+//! generation has to be restarted, but no party ends up with a wrong public
+//! key. A signer that aborts before its nonce dealing is complete has to be
+//! left out of the signer set and signing restarted; after the nonce round any
+//! `t` partial signatures are enough.
+//!
+//! All messages are plain structs; the caller provides an authenticated
+//! broadcast channel and private point-to-point channels.
+
+use std::fmt;
+
+use rand::{CryptoRng, RngCore};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::error::{Sm2Error, Sm2Result};
+use crate::fields::fn64::{fn_add, fn_mul, fn_pow, fn_sub, SM2_N_MINUS_TWO};
+use gm_sm3::Sm3;
+
+use crate::fields::fp64::{fp_from_mont, random_u256_with_rng};
+use crate::fields::FieldModOperation;
+use crate::key::Sm2PublicKey;
+use crate::p256_ecc::{g_mul, g_mul_ct, multi_scalar_mul, Point};
+use crate::u256::{u256_from_be_bytes, SM2_ONE, SM2_ZERO, U256};
+
+/// One Shamir share `f(index)`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Share {
+    pub index: u32,
+    pub value: U256,
+}
+
+/// Split `secret` into `n` shares, any `t` of which reconstruct it. Returns the
+/// Feldman commitments `[c_m]G` to the polynomial coefficients and the shares
+/// for the indices `1..=n`.
+pub fn vss_share(secret: &U256, t: usize, n: usize) -> Sm2Result<(Vec<Point>, Vec<Share>)> {
+    vss_share_with_rng(&mut rand::thread_rng(), secret, t, n)
+}
+
+pub fn vss_share_with_rng<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    secret: &U256,
+    t: usize,
+    n: usize,
+) -> Sm2Result<(Vec<Point>, Vec<Share>)> {
+    if t == 0 || t > n || n > u32::MAX as usize {
+        return Err(Sm2Error::InvalidThreshold);
+    }
+    let poly = Polynomial::random_with_rng(rng, secret, t - 1);
+    let shares = (1..=n as u32)
+        .map(|index| Share {
+            index,
+            value: poly.evaluate(index),
+        })
+        .collect();
+    Ok((poly.commit(None), shares))
+}
+
+/// Check a share against the dealer's Feldman commitments.
+pub fn vss_verify(commitments: &[Point], share: &Share) -> bool {
+    verify_share(commitments, None, share.index, &share.value)
+}
+
+/// Lagrange interpolation of the secret from at least `t` shares with distinct,
+/// non-zero indices.
+pub fn vss_reconstruct(shares: &[Share]) -> Sm2Result<U256> {
+    let points: Vec<(u32, U256)> = shares.iter().map(|s| (s.index, s.value)).collect();
+    check_indices(points.iter().map(|p| p.0))?;
+    Ok(interpolate(&points, &SM2_ZERO))
+}
+
+/// Round 1 broadcast of a key generation dealer.
+#[derive(Debug, Clone)]
+pub struct DkgCommitments {
+    pub from: u32,
+    /// commitments over `G` to the sharing of `w`
+    pub w: Vec<Point>,
+    /// commitments over `G` to the sharing of the mask `a`
+    pub a: Vec<Point>,
+    /// commitments over `G` to the non-constant coefficients of the zero sharing
+    pub z: Vec<Point>,
+}
+
+/// Round 1 private message from dealer `from` to party `to`.
+#[derive(Clone)]
+pub struct DkgShares {
+    pub from: u32,
+    pub to: u32,
+    pub w: U256,
+    pub a: U256,
+    pub z: U256,
+}
+
+/// Round 2 broadcast: the party's share of the masked product `w * a`.
+#[derive(Debug, Clone, Copy)]
+pub struct DkgProduct {
+    pub from: u32,
+    pub value: U256,
+    /// `[w_j]A` for the mask commitment `A = [a]G`
+    pub masked: Point,
+    /// proof that `masked` and the Feldman commitment `[w_j]G` share `w_j`
+    pub proof: DleqProof,
+}
+
+/// Chaum-Pedersen proof of equal discrete logarithms `log_G(X) == log_A(Y)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DleqProof {
+    pub c: U256,
+    pub s: U256,
+}
+
+/// One participant of the distributed key generation.
+pub struct DkgParty {
+    index: u32,
+    t: usize,
+    n: usize,
+    w_poly: Polynomial,
+    a_poly: Polynomial,
+    z_poly: Polynomial,
+    w: U256,
+    w_commitments: Vec<Point>,
+    a_commitment: Point,
+}
+
+/// A party's share of the threshold key, the output of key generation.
+#[derive(Clone)]
+pub struct ThresholdKeyShare {
+    index: u32,
+    t: usize,
+    n: usize,
+    w: U256,
+    q: Point,
+    public_key: Sm2PublicKey,
+}
+
+/// Signing round 1 broadcast: Feldman commitments over `Q` to the nonce sharing.
+#[derive(Debug, Clone)]
+pub struct NonceCommitments {
+    pub from: u32,
+    pub commitments: Vec<Point>,
+}
+
+/// Signing round 1 private message from signer `from` to signer `to`.
+#[derive(Clone)]
+pub struct NonceShare {
+    pub from: u32,
+    pub to: u32,
+    pub value: U256,
+}
+
+/// Signing round 2 broadcast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialSignature {
+    pub from: u32,
+    pub r: U256,
+    pub s: U256,
+}
+
+/// A signer's state across the signing rounds.
+pub struct SigningSession {
+    index: u32,
+    t: usize,
+    signers: Vec<u32>,
+    w: U256,
+    q: Point,
+    public_key: Sm2PublicKey,
+    e: [u8; 32],
+    u_poly: Polynomial,
+    r: Option<U256>,
+}
+
+impl DkgParty {
+    /// Start key generation as party `index` of `n` (indices are `1..=n`) with
+    /// threshold `t`; requires `2t - 1 <= n`. Broadcast the commitments and send
+    /// each share to its recipient.
+    pub fn new(
+        index: u32,
+        t: usize,
+        n: usize,
+    ) -> Sm2Result<(DkgParty, DkgCommitments, Vec<DkgShares>)> {
+        Self::new_with_rng(&mut rand::thread_rng(), index, t, n)
+    }
+
+    pub fn new_with_rng<R: CryptoRng + RngCore>(
+        rng: &mut R,
+        index: u32,
+        t: usize,
+        n: usize,
+    ) -> Sm2Result<(DkgParty, DkgCommitments, Vec<DkgShares>)> {
+        if t == 0 || 2 * t - 1 > n || n > u32::MAX as usize {
+            return Err(Sm2Error::InvalidThreshold);
+        }
+        if index == 0 || index as usize > n {
+            return Err(Sm2Error::InvalidShare);
+        }
+        let w_poly = Polynomial::random_secret_with_rng(rng, t - 1);
+        let a_poly = Polynomial::random_secret_with_rng(rng, t - 1);
+        let z_poly = Polynomial::random_with_rng(rng, &SM2_ZERO, 2 * t - 2);
+
+        let commitments = DkgCommitments {
+            from: index,
+            w: w_poly.commit(None),
+            a: a_poly.commit(None),
+            z: z_poly.commit(None)[1..].to_vec(),
+        };
+        let shares = (1..=n as u32)
+            .map(|to| DkgShares {
+                from: index,
+                to,
+                w: w_poly.evaluate(to),
+                a: a_poly.evaluate(to),
+                z: z_poly.evaluate(to),
+            })
+            .collect();
+        let party = DkgParty {
+            index,
+            t,
+            n,
+            w_poly,
+            a_poly,
+            z_poly,
+            w: SM2_ZERO,
+            w_commitments: Vec::new(),
+            a_commitment: Point::zero(),
+        };
+        Ok((party, commitments, shares))
+    }
+
+    /// Round 2: verify the dealings of every party that broadcast commitments
+    /// and publish this party's share of the masked product. `shares` are the
+    /// private messages addressed to this party.
+    pub fn round2(
+        &mut self,
+        commitments: &[DkgCommitments],
+        shares: &[DkgShares],
+    ) -> Sm2Result<DkgProduct> {
+        self.round2_with_rng(&mut rand::thread_rng(), commitments, shares)
+    }
+
+    pub fn round2_with_rng<R: CryptoRng + RngCore>(
+        &mut self,
+        rng: &mut R,
+        commitments: &[DkgCommitments],
+        shares: &[DkgShares],
+    ) -> Sm2Result<DkgProduct> {
+        check_indices(commitments.iter().map(|c| c.from))?;
+        if commitments.iter().all(|c| c.from != self.index) {
+            return Err(Sm2Error::NotEnoughShares);
+        }
+        let mut w = SM2_ZERO;
+        let mut a = SM2_ZERO;
+        let mut z = SM2_ZERO;
+        let mut w_commitments = vec![Point::zero(); self.t];
+        let mut a_commitment = Point::zero();
+        for dealer in commitments {
+            if dealer.from as usize > self.n
+                || dealer.w.len() != self.t
+                || dealer.a.len() != self.t
+                || dealer.z.len() != 2 * self.t - 2
+            {
+                return Err(Sm2Error::InvalidShare);
+            }
+            let share = shares
+                .iter()
+                .find(|s| s.from == dealer.from && s.to == self.index)
+                .ok_or(Sm2Error::NotEnoughShares)?;
+            let mut z_commitments = vec![Point::zero()];
+            z_commitments.extend_from_slice(&dealer.z);
+            if !verify_share(&dealer.w, None, self.index, &share.w)
+                || !verify_share(&dealer.a, None, self.index, &share.a)
+                || !verify_share(&z_commitments, None, self.index, &share.z)
+            {
+                return Err(Sm2Error::InvalidShare);
+            }
+            w = fn_add(&w, &share.w);
+            a = fn_add(&a, &share.a);
+            z = fn_add(&z, &share.z);
+            for (sum, c) in w_commitments.iter_mut().zip(dealer.w.iter()) {
+                *sum = sum.point_add(c);
+            }
+            a_commitment = a_commitment.point_add(&dealer.a[0]);
+        }
+        self.w = w;
+        self.w_commitments = w_commitments;
+        self.a_commitment = a_commitment;
+        let value = fn_add(&fn_mul(&w, &a), &z);
+        let masked = a_commitment.scalar_mul_ct(&w);
+        let proof = dleq_prove(rng, self.index, &w, &a_commitment, &masked);
+        w.zeroize();
+        a.zeroize();
+        z.zeroize();
+        Ok(DkgProduct {
+            from: self.index,
+            value,
+            masked,
+            proof,
+        })
+    }
+
+    /// Round 3: open `v = w * a` from at least `2t - 1` products and derive the
+    /// public key. Products beyond `2t - 1` are checked for consistency, and
+    /// the opened `v` is checked against the proven `[w_j]A`.
+    pub fn finish(self, products: &[DkgProduct]) -> Sm2Result<ThresholdKeyShare> {
+        let v = self.open_product(products)?;
+        let q = self
+            .a_commitment
+            .scalar_mul(&fn_pow(&v, &SM2_N_MINUS_TWO))
+            .to_affine_point();
+        let p = q.point_add(&g_mul(&SM2_ONE).neg());
+        if q.is_zero() || p.is_zero() {
+            return Err(Sm2Error::InvalidPublic);
+        }
+        Ok(ThresholdKeyShare {
+            index: self.index,
+            t: self.t,
+            n: self.n,
+            w: self.w,
+            q,
+            public_key: Sm2PublicKey {
+                point: p.to_affine_point(),
+            },
+        })
+    }
+
+    /// The verified opening of `v = w * a`.
+    fn open_product(&self, products: &[DkgProduct]) -> Sm2Result<U256> {
+        let points: Vec<(u32, U256)> = products.iter().map(|p| (p.from, p.value)).collect();
+        check_indices(points.iter().map(|p| p.0))?;
+        let needed = 2 * self.t - 1;
+        if points.len() < needed {
+            return Err(Sm2Error::NotEnoughShares);
+        }
+        for product in products {
+            let w_commitment = evaluate_commitments(&self.w_commitments, product.from);
+            if product.from as usize > self.n
+                || !dleq_verify(
+                    product.from,
+                    &w_commitment,
+                    &self.a_commitment,
+                    &product.masked,
+                    &product.proof,
+                )
+            {
+                return Err(Sm2Error::InvalidShare);
+            }
+        }
+        let (base, extra) = points.split_at(needed);
+        for (x, y) in extra {
+            if interpolate(base, &scalar(*x)) != *y {
+                return Err(Sm2Error::InvalidShare);
+            }
+        }
+        let v = interpolate(base, &SM2_ZERO);
+        if v.is_zero() {
+            return Err(Sm2Error::InvalidShare);
+        }
+        // [v]G == [w * a]G, the latter interpolated from the proven [w_j]A
+        let indices: Vec<u32> = base.iter().map(|p| p.0).collect();
+        let lambdas: Vec<U256> = (0..indices.len())
+            .map(|i| lagrange(&indices, i, &SM2_ZERO))
+            .collect();
+        let masked: Vec<Point> = products[..needed].iter().map(|p| p.masked).collect();
+        if !same_point(&g_mul(&v), &multi_scalar_mul(&masked, &lambdas)) {
+            return Err(Sm2Error::InvalidShare);
+        }
+        Ok(v)
+    }
+}
+
+impl ThresholdKeyShare {
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.t
+    }
+
+    pub fn public_key(&self) -> &Sm2PublicKey {
+        &self.public_key
+    }
+
+    /// Signing round 1: join the signer set `signers` (at least `t` distinct
+    /// indices, including this party) to sign `msg`. Broadcast the commitments
+    /// and send each nonce share to its recipient.
+    pub fn sign_round1(
+        &self,
+        signers: &[u32],
+        id: Option<&[u8]>,
+        msg: &[u8],
+    ) -> Sm2Result<(SigningSession, NonceCommitments, Vec<NonceShare>)> {
+        self.sign_round1_with_rng(&mut rand::thread_rng(), signers, id, msg)
+    }
+
+    pub fn sign_round1_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        signers: &[u32],
+        id: Option<&[u8]>,
+        msg: &[u8],
+    ) -> Sm2Result<(SigningSession, NonceCommitments, Vec<NonceShare>)> {
+        check_indices(signers.iter().copied())?;
+        if signers.len() < self.t || !signers.contains(&self.index) {
+            return Err(Sm2Error::NotEnoughShares);
+        }
+        if signers.iter().any(|&j| j as usize > self.n) {
+            return Err(Sm2Error::InvalidShare);
+        }
+        let mut hasher = self.public_key.za_hasher(id)?;
+        hasher.update(msg);
+        let e = hasher.finalize();
+
+        let u_poly = Polynomial::random_secret_with_rng(rng, self.t - 1);
+        let commitments = NonceCommitments {
+            from: self.index,
+            commitments: u_poly.commit(Some(&self.q)),
+        };
+        let shares = signers
+            .iter()
+            .map(|&to| NonceShare {
+                from: self.index,
+                to,
+                value: u_poly.evaluate(to),
+            })
+            .collect();
+        let session = SigningSession {
+            index: self.index,
+            t: self.t,
+            signers: signers.to_vec(),
+            w: self.w,
+            q: self.q,
+            public_key: self.public_key,
+            e,
+            u_poly,
+            r: None,
+        };
+        Ok((session, commitments, shares))
+    }
+}
+
+impl SigningSession {
+    /// Signing round 2: verify the nonce dealings of all signers and publish
+    /// the partial signature `s_j = u_j + r * w_j`.
+    pub fn sign_round2(
+        &mut self,
+        commitments: &[NonceCommitments],
+        shares: &[NonceShare],
+    ) -> Sm2Result<PartialSignature> {
+        let mut u = Zeroizing::new(SM2_ZERO);
+        let mut k_point = Point::zero();
+        for &dealer in &self.signers {
+            let c = commitments
+                .iter()
+                .find(|c| c.from == dealer)
+                .ok_or(Sm2Error::NotEnoughShares)?;
+            let share = shares
+                .iter()
+                .find(|s| s.from == dealer && s.to == self.index)
+                .ok_or(Sm2Error::NotEnoughShares)?;
+            if c.commitments.len() != self.t
+                || !verify_share(&c.commitments, Some(&self.q), self.index, &share.value)
+            {
+                return Err(Sm2Error::InvalidShare);
+            }
+            *u = fn_add(&u, &share.value);
+            k_point = k_point.point_add(&c.commitments[0]);
+        }
+        if k_point.is_zero() {
+            return Err(Sm2Error::ZeroPoint);
+        }
+        let x1 = fp_from_mont(&k_point.to_affine_point().x);
+        let r = fn_add(&u256_from_be_bytes(&self.e), &x1);
+        if r.is_zero() {
+            return Err(Sm2Error::ZeroSig);
+        }
+        self.r = Some(r);
+        // the nonce polynomial is not needed any more
+        self.u_poly.zeroize();
+        Ok(PartialSignature {
+            from: self.index,
+            r,
+            s: fn_add(&u, &fn_mul(&r, &self.w)),
+        })
+    }
+
+    /// Combine at least `t` partial signatures from the signer set into the
+    /// 64-byte `r || s` signature and verify it against the public key.
+    pub fn combine(&self, partials: &[PartialSignature]) -> Sm2Result<Vec<u8>> {
+        let r = self.r.ok_or(Sm2Error::NotEnoughShares)?;
+        check_indices(partials.iter().map(|p| p.from))?;
+        if partials
+            .iter()
+            .any(|p| p.r != r || !self.signers.contains(&p.from))
+        {
+            return Err(Sm2Error::InvalidShare);
+        }
+        if partials.len() < self.t {
+            return Err(Sm2Error::NotEnoughShares);
+        }
+        let points: Vec<(u32, U256)> = partials.iter().map(|p| (p.from, p.s)).collect();
+        let (base, extra) = points.split_at(self.t);
+        for (x, y) in extra {
+            if interpolate(base, &scalar(*x)) != *y {
+                return Err(Sm2Error::InvalidShare);
+            }
+        }
+        // s + r = w * (k + r)
+        let s_r = interpolate(base, &SM2_ZERO);
+        let s = fn_sub(&s_r, &r);
+        if s.is_zero() || s_r.is_zero() {
+            return Err(Sm2Error::ZeroSig);
+        }
+        let mut sig = Vec::with_capacity(64);
+        sig.extend_from_slice(&r.to_byte_be());
+        sig.extend_from_slice(&s.to_byte_be());
+        self.public_key
+            .verify_digest(&self.e, &sig)
+            .map_err(|_| Sm2Error::InvalidSignature)?;
+        Ok(sig)
+    }
+}
+
+/// Secret polynomial over the scalar field, `coefficients[0]` is the constant.
+struct Polynomial {
+    coefficients: Vec<U256>,
+}
+
+impl Polynomial {
+    fn random_with_rng<R: CryptoRng + RngCore>(
+        rng: &mut R,
+        constant: &U256,
+        degree: usize,
+    ) -> Polynomial {
+        let mut coefficients = Vec::with_capacity(degree + 1);
+        coefficients.push(*constant);
+        for _ in 0..degree {
+            coefficients.push(random_u256_with_rng(rng));
+        }
+        Polynomial { coefficients }
+    }
+
+    /// A polynomial with a random constant term.
+    fn random_secret_with_rng<R: CryptoRng + RngCore>(rng: &mut R, degree: usize) -> Polynomial {
+        let constant = Zeroizing::new(random_u256_with_rng(rng));
+        Self::random_with_rng(rng, &constant, degree)
+    }
+
+    fn evaluate(&self, x: u32) -> U256 {
+        let x = scalar(x);
+        let mut y = SM2_ZERO;
+        for c in self.coefficients.iter().rev() {
+            y = fn_add(&fn_mul(&y, &x), c);
+        }
+        y
+    }
+
+    /// `[c_m]base` for every coefficient, `base` defaults to `G`.
+    fn commit(&self, base: Option<&Point>) -> Vec<Point> {
+        self.coefficients
+            .iter()
+            .map(|c| match base {
+                None => g_mul_ct(c),
+                Some(b) => b.scalar_mul_ct(c),
+            })
+            .map(|p| if p.is_zero() { p } else { p.to_affine_point() })
+            .collect()
+    }
+}
+
+impl Zeroize for Polynomial {
+    fn zeroize(&mut self) {
+        self.coefficients.zeroize();
+    }
+}
+
+impl Drop for Polynomial {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+fn scalar(x: u32) -> U256 {
+    [x as u64, 0, 0, 0]
+}
+
+fn check_indices<I: Iterator<Item = u32>>(indices: I) -> Sm2Result<()> {
+    let mut seen: Vec<u32> = Vec::new();
+    for i in indices {
+        if i == 0 || seen.contains(&i) {
+            return Err(Sm2Error::InvalidShare);
+        }
+        seen.push(i);
+    }
+    Ok(())
+}
+
+/// `sum([index^m]C_m)`, the commitment to the share of party `index`
+fn evaluate_commitments(commitments: &[Point], index: u32) -> Point {
+    let x = scalar(index);
+    let mut expected = Point::zero();
+    for c in commitments.iter().rev() {
+        expected = expected.scalar_mul(&x).point_add(c);
+    }
+    expected
+}
+
+/// `[value]base == sum([index^m]C_m)`
+fn verify_share(commitments: &[Point], base: Option<&Point>, index: u32, value: &U256) -> bool {
+    if commitments.is_empty() {
+        return false;
+    }
+    let expected = evaluate_commitments(commitments, index);
+    let actual = match base {
+        None => g_mul_ct(value),
+        Some(b) => b.scalar_mul_ct(value),
+    };
+    same_point(&expected, &actual)
+}
+
+fn same_point(a: &Point, b: &Point) -> bool {
+    if a.is_zero() || b.is_zero() {
+        return a.is_zero() && b.is_zero();
+    }
+    a.to_affine_point() == b.to_affine_point()
+}
+
+/// Evaluate at `x` the polynomial through `points`, whose indices are distinct.
+fn interpolate(points: &[(u32, U256)], x: &U256) -> U256 {
+    let indices: Vec<u32> = points.iter().map(|p| p.0).collect();
+    let mut y = SM2_ZERO;
+    for (i, (_, yi)) in points.iter().enumerate() {
+        y = fn_add(&y, &fn_mul(&lagrange(&indices, i, x), yi));
+    }
+    y
+}
+
+/// The Lagrange basis polynomial of `indices[i]` evaluated at `x`.
+fn lagrange(indices: &[u32], i: usize, x: &U256) -> U256 {
+    let xi = scalar(indices[i]);
+    let mut num = SM2_ONE;
+    let mut den = SM2_ONE;
+    for (j, xj) in indices.iter().enumerate() {
+        if i != j {
+            let xj = scalar(*xj);
+            num = fn_mul(&num, &fn_sub(x, &xj));
+            den = fn_mul(&den, &fn_sub(&xi, &xj));
+        }
+    }
+    fn_mul(&num, &fn_pow(&den, &SM2_N_MINUS_TWO))
+}
+
+/// Prove that `y = [x]a` uses the same `x` as `[x]G`.
+fn dleq_prove<R: CryptoRng + RngCore>(
+    rng: &mut R,
+    from: u32,
+    x: &U256,
+    a: &Point,
+    y: &Point,
+) -> DleqProof {
+    let k = Zeroizing::new(random_u256_with_rng(rng));
+    let r1 = g_mul_ct(&k);
+    let r2 = a.scalar_mul_ct(&k);
+    let c = dleq_challenge(from, &g_mul(x), a, y, &r1, &r2);
+    DleqProof {
+        c,
+        s: fn_add(&k, &fn_mul(&c, x)),
+    }
+}
+
+/// Check `[s]G == R1 + [c]X` and `[s]A == R2 + [c]Y` for the `R1`, `R2` that
+/// hash to `c`.
+fn dleq_verify(from: u32, x: &Point, a: &Point, y: &Point, proof: &DleqProof) -> bool {
+    let r1 = g_mul(&proof.s).point_add(&x.scalar_mul(&proof.c).neg());
+    let r2 = a
+        .scalar_mul(&proof.s)
+        .point_add(&y.scalar_mul(&proof.c).neg());
+    dleq_challenge(from, x, a, y, &r1, &r2) == proof.c
+}
+
+fn dleq_challenge(from: u32, x: &Point, a: &Point, y: &Point, r1: &Point, r2: &Point) -> U256 {
+    let mut hasher = Sm3::new();
+    hasher.update(b"gm-sm2 threshold dleq");
+    hasher.update(&from.to_be_bytes());
+    for p in [x, a, y, r1, r2] {
+        if p.is_zero() {
+            hasher.update(&[0x00]);
+        } else {
+            hasher.update(&p.to_byte_be(false));
+        }
+    }
+    fn_add(&u256_from_be_bytes(&hasher.finalize()), &SM2_ZERO)
+}
+
+impl fmt::Debug for Share {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Share")
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for DkgShares {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DkgShares")
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for NonceShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NonceShare")
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for DkgParty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DkgParty")
+            .field("index", &self.index)
+            .field("t", &self.t)
+            .field("n", &self.n)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for ThresholdKeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThresholdKeyShare")
+            .field("index", &self.index)
+            .field("t", &self.t)
+            .field("n", &self.n)
+            .field("public_key", &self.public_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for SigningSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningSession")
+            .field("index", &self.index)
+            .field("signers", &self.signers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Zeroize for Share {
+    fn zeroize(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Share {}
+
+impl Zeroize for DkgShares {
+    fn zeroize(&mut self) {
+        self.w.zeroize();
+        self.a.zeroize();
+        self.z.zeroize();
+    }
+}
+
+impl Drop for DkgShares {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for DkgShares {}
+
+impl Zeroize for NonceShare {
+    fn zeroize(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl Drop for NonceShare {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for NonceShare {}
+
+impl Zeroize for DkgParty {
+    fn zeroize(&mut self) {
+        self.w_poly.zeroize();
+        self.a_poly.zeroize();
+        self.z_poly.zeroize();
+        self.w.zeroize();
+    }
+}
+
+impl Drop for DkgParty {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for DkgParty {}
+
+impl Zeroize for ThresholdKeyShare {
+    fn zeroize(&mut self) {
+        self.w.zeroize();
+    }
+}
+
+impl Drop for ThresholdKeyShare {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for ThresholdKeyShare {}
+
+impl Zeroize for SigningSession {
+    fn zeroize(&mut self) {
+        self.w.zeroize();
+        self.u_poly.zeroize();
+    }
+}
+
+impl Drop for SigningSession {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SigningSession {}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::error::Sm2Error;
+    use crate::fields::fn64::{fn_pow, fn_sub, SM2_N_MINUS_TWO};
+    use crate::fields::FieldModOperation;
+    use crate::key::Sm2PrivateKey;
+    use crate::threshold::{
+        vss_reconstruct, vss_share, vss_verify, DkgCommitments, DkgParty, DkgProduct, DkgShares,
+        NonceCommitments, NonceShare, PartialSignature, Share, SigningSession, ThresholdKeyShare,
+    };
+    use crate::u256::{SM2_ONE, U256};
+
+    /// Run key generation for `parties`; the ones in `abort` never send anything.
+    fn dkg(t: usize, n: usize, abort: &[u32]) -> Vec<ThresholdKeyShare> {
+        let mut parties = Vec::new();
+        let mut commitments: Vec<DkgCommitments> = Vec::new();
+        let mut shares: Vec<DkgShares> = Vec::new();
+        for i in 1..=n as u32 {
+            let (party, c, s) = DkgParty::new(i, t, n).unwrap();
+            if !abort.contains(&i) {
+                parties.push(party);
+                commitments.push(c);
+                shares.extend(s);
+            }
+        }
+        let products: Vec<_> = parties
+            .iter_mut()
+            .map(|p| p.round2(&commitments, &shares).unwrap())
+            .collect();
+        parties
+            .into_iter()
+            .map(|p| p.finish(&products).unwrap())
+            .collect()
+    }
+
+    fn key(shares: &[ThresholdKeyShare], index: u32) -> &ThresholdKeyShare {
+        shares.iter().find(|k| k.index() == index).unwrap()
+    }
+
+    fn nonce_round(
+        keys: &[ThresholdKeyShare],
+        signers: &[u32],
+        msg: &[u8],
+    ) -> (Vec<SigningSession>, Vec<NonceCommitments>, Vec<NonceShare>) {
+        let mut sessions = Vec::new();
+        let mut commitments = Vec::new();
+        let mut shares = Vec::new();
+        for &i in signers {
+            let (session, c, s) = key(keys, i).sign_round1(signers, None, msg).unwrap();
+            sessions.push(session);
+            commitments.push(c);
+            shares.extend(s);
+        }
+        (sessions, commitments, shares)
+    }
+
+    fn sign(keys: &[ThresholdKeyShare], signers: &[u32], msg: &[u8]) -> Vec<u8> {
+        let (mut sessions, commitments, shares) = nonce_round(keys, signers, msg);
+        let partials: Vec<PartialSignature> = sessions
+            .iter_mut()
+            .map(|s| s.sign_round2(&commitments, &shares).unwrap())
+            .collect();
+        let sig = sessions[0].combine(&partials).unwrap();
+        for s in &sessions[1..] {
+            assert_eq!(sig, s.combine(&partials).unwrap());
+        }
+        sig
+    }
+
+    #[test]
+    fn test_vss() {
+        let secret: U256 = [0x1234, 0x5678, 0x9abc, 0xdef0];
+        let (commitments, shares) = vss_share(&secret, 3, 5).unwrap();
+        for share in &shares {
+            assert!(vss_verify(&commitments, share));
+        }
+        assert_eq!(secret, vss_reconstruct(&shares[..3]).unwrap());
+        assert_eq!(secret, vss_reconstruct(&shares[2..]).unwrap());
+        let subset = [shares[4].clone(), shares[0].clone(), shares[2].clone()];
+        assert_eq!(secret, vss_reconstruct(&subset).unwrap());
+        assert_ne!(secret, vss_reconstruct(&shares[..2]).unwrap());
+
+        let mut bad = shares[1].clone();
+        bad.value[0] ^= 1;
+        assert!(!vss_verify(&commitments, &bad));
+        let bad = Share {
+            index: 6,
+            value: shares[1].value,
+        };
+        assert!(!vss_verify(&commitments, &bad));
+
+        let duplicate = [shares[0].clone(), shares[0].clone()];
+        assert_eq!(vss_reconstruct(&duplicate), Err(Sm2Error::InvalidShare));
+        assert_eq!(
+            vss_share(&secret, 4, 3).unwrap_err(),
+            Sm2Error::InvalidThreshold
+        );
+    }
+
+    #[test]
+    fn test_dkg_public_key() {
+        let keys = dkg(2, 3, &[]);
+        let pk = *keys[0].public_key();
+        assert!(pk.is_valid());
+        for k in &keys {
+            assert_eq!(pk.to_bytes(false), k.public_key().to_bytes(false));
+        }
+
+        // only here, the shares of w = (1 + d)^-1 are combined to check the key
+        let shares: Vec<Share> = keys
+            .iter()
+            .map(|k| Share {
+                index: k.index,
+                value: k.w,
+            })
+            .collect();
+        let w = vss_reconstruct(&shares[1..]).unwrap();
+        let d = fn_sub(&fn_pow(&w, &SM2_N_MINUS_TWO), &SM2_ONE);
+        let sk = Sm2PrivateKey::new(&d.to_byte_be()).unwrap();
+        assert_eq!(pk.to_bytes(false), sk.public_key.to_bytes(false));
+    }
+
+    #[test]
+    fn test_threshold_sign() {
+        let keys = dkg(3, 5, &[]);
+        let pk = *keys[0].public_key();
+        for signers in [&[1, 2, 3][..], &[5, 3, 1], &[2, 3, 4, 5], &[1, 2, 3, 4, 5]] {
+            let sig = sign(&keys, signers, b"threshold");
+            pk.verify(None, b"threshold", &sig).unwrap();
+        }
+
+        let keys = dkg(1, 1, &[]);
+        let sig = sign(&keys, &[1], b"1-of-1");
+        keys[0].public_key().verify(None, b"1-of-1", &sig).unwrap();
+    }
+
+    #[test]
+    fn test_dkg_aborting_and_cheating_dealer() {
+        // party 4 never shows up, the other three still reach 2-of-4
+        let keys = dkg(2, 4, &[4]);
+        assert_eq!(keys.len(), 3);
+        let sig = sign(&keys, &[1, 3], b"abc");
+        keys[0].public_key().verify(None, b"abc", &sig).unwrap();
+
+        // a dealer sends a share that does not match its commitments
+        let mut rng = StdRng::seed_from_u64(19);
+        let mut commitments = Vec::new();
+        let mut shares = Vec::new();
+        let mut parties = Vec::new();
+        for i in 1..=3 {
+            let (party, c, s) = DkgParty::new_with_rng(&mut rng, i, 2, 3).unwrap();
+            parties.push(party);
+            commitments.push(c);
+            shares.extend(s);
+        }
+        let cheat = shares
+            .iter_mut()
+            .find(|s| s.from == 2 && s.to == 1)
+            .unwrap();
+        cheat.w[0] ^= 1;
+        assert_eq!(
+            parties[0].round2(&commitments, &shares).unwrap_err(),
+            Sm2Error::InvalidShare
+        );
+        assert!(parties[2].round2(&commitments, &shares).is_ok());
+
+        // too few products to open the masked product
+        let products = vec![parties[2].round2(&commitments, &shares).unwrap()];
+        assert_eq!(
+            parties.pop().unwrap().finish(&products).unwrap_err(),
+            Sm2Error::NotEnoughShares
+        );
+        assert_eq!(
+            DkgParty::new(1, 3, 4).unwrap_err(),
+            Sm2Error::InvalidThreshold
+        );
+    }
+
+    #[test]
+    fn test_dkg_cheating_product() {
+        // 3-of-5 opens the product from exactly 2t - 1 = 5 shares, so there is
+        // no extra share to cross-check against
+        let mut commitments = Vec::new();
+        let mut shares = Vec::new();
+        let mut parties = Vec::new();
+        for i in 1..=5 {
+            let (party, c, s) = DkgParty::new(i, 3, 5).unwrap();
+            parties.push(party);
+            commitments.push(c);
+            shares.extend(s);
+        }
+        let products: Vec<DkgProduct> = parties
+            .iter_mut()
+            .map(|p| p.round2(&commitments, &shares).unwrap())
+            .collect();
+
+        let mut bad_value = products.clone();
+        bad_value[2].value[0] ^= 1;
+        // a wrong [w_j]A does not match its proof
+        let mut bad_masked = products.clone();
+        bad_masked[4].masked = products[3].masked;
+        let mut bad_proof = products.clone();
+        bad_proof[0].proof = products[1].proof;
+        for bad in [bad_value, bad_masked, bad_proof] {
+            for party in &parties {
+                assert_eq!(
+                    party.open_product(&bad).unwrap_err(),
+                    Sm2Error::InvalidShare
+                );
+            }
+        }
+
+        let keys: Vec<ThresholdKeyShare> = parties
+            .into_iter()
+            .map(|p| p.finish(&products).unwrap())
+            .collect();
+        let sig = sign(&keys, &[1, 4, 5], b"abc");
+        keys[0].public_key().verify(None, b"abc", &sig).unwrap();
+    }
+
+    #[test]
+    fn test_sign_with_aborting_parties() {
+        let keys = dkg(2, 3, &[]);
+        let pk = *keys[0].public_key();
+
+        // signer 3 aborts in the nonce round: the others cannot finish and
+        // restart without it
+        let (mut sessions, mut commitments, mut shares) = nonce_round(&keys, &[1, 2, 3], b"m");
+        commitments.retain(|c| c.from != 3);
+        shares.retain(|s| s.from != 3);
+        assert_eq!(
+            sessions[0].sign_round2(&commitments, &shares).unwrap_err(),
+            Sm2Error::NotEnoughShares
+        );
+        let sig = sign(&keys, &[1, 2], b"m");
+        pk.verify(None, b"m", &sig).unwrap();
+
+        // signer 1 aborts after the nonce round: two partials are enough
+        let (mut sessions, commitments, shares) = nonce_round(&keys, &[1, 2, 3], b"m");
+        let partials: Vec<_> = sessions[1..]
+            .iter_mut()
+            .map(|s| s.sign_round2(&commitments, &shares).unwrap())
+            .collect();
+        let sig = sessions[1].combine(&partials).unwrap();
+        pk.verify(None, b"m", &sig).unwrap();
+        assert_eq!(
+            sessions[1].combine(&partials[..1]).unwrap_err(),
+            Sm2Error::NotEnoughShares
+        );
+    }
+
+    #[test]
+    fn test_sign_with_bad_partials() {
+        let keys = dkg(2, 3, &[]);
+        let (mut sessions, commitments, shares) = nonce_round(&keys, &[1, 2, 3], b"m");
+
+        // a nonce share that does not match the commitments
+        let mut forged = shares.clone();
+        forged
+            .iter_mut()
+            .find(|s| s.from == 1 && s.to == 2)
+            .unwrap()
+            .value[0] ^= 1;
+        assert_eq!(
+            sessions[1].sign_round2(&commitments, &forged).unwrap_err(),
+            Sm2Error::InvalidShare
+        );
+
+        let mut partials: Vec<_> = sessions
+            .iter_mut()
+            .map(|s| s.sign_round2(&commitments, &shares).unwrap())
+            .collect();
+        partials[2].s[0] ^= 1;
+        // the extra partial exposes the inconsistency
+        assert_eq!(
+            sessions[0].combine(&partials).unwrap_err(),
+            Sm2Error::InvalidShare
+        );
+        // with exactly t partials the final verification catches it
+        assert_eq!(
+            sessions[0].combine(&partials[1..]).unwrap_err(),
+            Sm2Error::InvalidSignature
+        );
+        assert!(sessions[0].combine(&partials[..2]).is_ok());
+    }
+}
@@ -101,6 +101,39 @@ pub fn u256_bits_and(a: &U256, b: &U256) -> U256 {
     result
 }
 
+/// All-ones mask if `choice == 1`, zero if `choice == 0`.
+#[inline(always)]
+pub(crate) fn ct_mask(choice: u64) -> u64 {
+    std::hint::black_box(0u64.wrapping_sub(choice & 1))
+}
+
+/// 1 if `a == b` else 0, without branching on the values.
+#[inline(always)]
+pub(crate) fn ct_eq_u64(a: u64, b: u64) -> u64 {
+    let x = a ^ b;
+    ((x | x.wrapping_neg()) >> 63) ^ 1
+}
+
+/// `a` if `choice == 0`, `b` if `choice == 1`, without branching on `choice`.
+#[inline(always)]
+pub(crate) fn u256_select(a: &U256, b: &U256, choice: u64) -> U256 {
+    let mask = ct_mask(choice);
+    [
+        a[0] ^ (mask & (a[0] ^ b[0])),
+        a[1] ^ (mask & (a[1] ^ b[1])),
+        a[2] ^ (mask & (a[2] ^ b[2])),
+        a[3] ^ (mask & (a[3] ^ b[3])),
+    ]
+}
+
+/// Reduce `carry * 2^256 + r`, known to be below `2 * m`, into `[0, m)` without
+/// branching on the value.
+#[inline(always)]
+pub(crate) fn u256_reduce_once(r: &U256, carry: bool, m: &U256) -> U256 {
+    let (diff, borrow) = u256_sub(r, m);
+    u256_select(r, &diff, carry as u64 | (!borrow) as u64)
+}
+
 #[inline(always)]
 pub fn u256_mul(a: &U256, b: &U256) -> U512 {
     let mut a_: [u64; 8] = [0; 8];
@@ -184,7 +217,7 @@ pub fn u256_from_be_bytes(input: &[u8]) -> U256 {
 mod test_operation {
     use num_bigint::BigUint;
 
-    use crate::u256::{u256_add, u256_mul, u256_sub};
+    use crate::u256::{u256_add, u256_mul, u256_reduce_once, u256_select, u256_sub};
 
     #[test]
     fn test_raw_add_u64() {
@@ -230,4 +263,21 @@ mod test_operation {
         mul.reverse();
         assert_eq!(r, *mul);
     }
+
+    #[test]
+    fn test_select_and_reduce() {
+        let a = [1, 2, 3, 4];
+        let b = [5, 6, 7, 8];
+        assert_eq!(a, u256_select(&a, &b, 0));
+        assert_eq!(b, u256_select(&a, &b, 1));
+
+        let m = [10, 0, 0, 1];
+        assert_eq!([9, 0, 0, 1], u256_reduce_once(&[9, 0, 0, 1], false, &m));
+        assert_eq!([0, 0, 0, 0], u256_reduce_once(&[10, 0, 0, 1], false, &m));
+        assert_eq!([5, 0, 0, 0], u256_reduce_once(&[15, 0, 0, 1], false, &m));
+        // 2^256 + 3 - m
+        let r = u256_reduce_once(&[3, 0, 0, 0], true, &m);
+        assert_eq!(u256_sub(&[3, 0, 0, 0], &m).0, r);
+        assert_eq!(([3, 0, 0, 0], true), u256_add(&r, &m));
+    }
 }
@@ -6,23 +6,23 @@ use crate::fields::FieldModOperation;
 use crate::fields::fp64::{fp_from_mont, SM2_G_X, SM2_G_Y, SM2_MODP_MONT_A, SM2_MODP_MONT_B};
 use crate::p256_ecc::Point;
 
-pub(crate) const DEFAULT_ID: &'static str = "1234567812345678";
+pub(crate) const DEFAULT_ID: &[u8] = b"1234567812345678";
 
 
-pub fn compute_za(id: &str, pk: &Point) -> Sm2Result<[u8; 32]> {
+/// ZA = SM3(ENTL || ID || a || b || xG || yG || xA || yA), the ID may be at most 8191 bytes
+/// since its bit length ENTL is encoded in two bytes.
+pub fn compute_za(id: &[u8], pk: &Point) -> Sm2Result<[u8; 32]> {
     if !pk.is_valid() {
         return Err(Sm2Error::InvalidPublic);
     }
     let mut prepend: Vec<u8> = Vec::new();
-    if id.len() * 8 > 65535 {
+    if id.len() > 8191 {
         return Err(Sm2Error::IdTooLong);
     }
     prepend
         .write_u16::<BigEndian>((id.len() * 8) as u16)
         .unwrap();
-    for c in id.bytes() {
-        prepend.push(c);
-    }
+    prepend.extend_from_slice(id);
 
     prepend.extend_from_slice(&fp_from_mont(&SM2_MODP_MONT_A).to_byte_be());
     prepend.extend_from_slice(&fp_from_mont(&SM2_MODP_MONT_B).to_byte_be());
@@ -47,31 +47,9 @@ pub fn xor_bytes(a: &[u8], b: &[u8]) -> Vec<u8> {
 }
 
 #[inline]
+#[deprecated(note = "use gm_sm3::sm3_kdf")]
 pub fn kdf(z: &[u8], klen: usize) -> Vec<u8> {
-    let mut ct = 0x00000001u32;
-    let bound = ((klen as f64) / 32.0).ceil() as u32;
-    let mut h_a = Vec::new();
-    for _i in 1..bound {
-        let mut prepend = Vec::new();
-        prepend.extend_from_slice(z);
-        prepend.extend_from_slice(&ct.to_be_bytes());
-
-        let h_a_i = sm3_hash(&prepend[..]);
-        h_a.extend_from_slice(&h_a_i);
-        ct += 1;
-    }
-
-    let mut prepend = Vec::new();
-    prepend.extend_from_slice(z);
-    prepend.extend_from_slice(&ct.to_be_bytes());
-
-    let last = sm3_hash(&prepend[..]);
-    if klen % 32 == 0 {
-        h_a.extend_from_slice(&last);
-    } else {
-        h_a.extend_from_slice(&last[0..(klen % 32)]);
-    }
-    h_a
+    gm_sm3::sm3_kdf(z, klen)
 }
 
 #[inline(always)]
@@ -0,0 +1,128 @@
+//! dudect-style timing leakage check for the constant-time scalar multiplications.
+//!
+//! Two classes of inputs are measured in random interleaved order: a fixed
+//! low-weight scalar and uniformly random scalars. Welch's t-test is applied to
+//! the timings after cropping the slowest samples at several percentiles; a
+//! `|t|` above 10 means the timing depends on the scalar.
+//!
+//! The measurements are noisy on shared machines, so the tests are ignored by
+//! default. Run them on an idle machine with:
+//!
+//! ```text
+//! cargo test --release -p gm-sm2 --test dudect -- --ignored --nocapture
+//! ```
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use gm_sm2::p256_ecc::{g_mul, g_mul_ct};
+use gm_sm2::u256::U256;
+use rand::{Rng, RngCore};
+
+const SAMPLES: usize = 20_000;
+const T_THRESHOLD: f64 = 10.0;
+const FIXED_SCALAR: U256 = [1, 0, 0, 0];
+
+#[derive(Default)]
+struct Welch {
+    n: [f64; 2],
+    mean: [f64; 2],
+    m2: [f64; 2],
+}
+
+impl Welch {
+    fn push(&mut self, class: usize, x: f64) {
+        self.n[class] += 1.0;
+        let delta = x - self.mean[class];
+        self.mean[class] += delta / self.n[class];
+        self.m2[class] += delta * (x - self.mean[class]);
+    }
+
+    fn t(&self) -> f64 {
+        let var0 = self.m2[0] / (self.n[0] - 1.0);
+        let var1 = self.m2[1] / (self.n[1] - 1.0);
+        (self.mean[0] - self.mean[1]) / (var0 / self.n[0] + var1 / self.n[1]).sqrt()
+    }
+}
+
+fn random_scalar<R: RngCore>(rng: &mut R) -> U256 {
+    [rng.next_u64(), rng.next_u64(), rng.next_u64(), rng.next_u64() >> 1]
+}
+
+/// Measure `f` on both classes and return the largest `|t|` over the cropped
+/// sample sets.
+fn max_t<F: Fn(&U256)>(f: F) -> f64 {
+    let mut rng = rand::thread_rng();
+    let inputs: Vec<(usize, U256)> = (0..SAMPLES)
+        .map(|_| {
+            let class = rng.gen_range(0..2);
+            let k = if class == 0 {
+                FIXED_SCALAR
+            } else {
+                random_scalar(&mut rng)
+            };
+            (class, k)
+        })
+        .collect();
+
+    // warm up caches and the branch predictor
+    for (_, k) in inputs.iter().take(100) {
+        f(k);
+    }
+
+    let timings: Vec<(usize, f64)> = inputs
+        .iter()
+        .map(|(class, k)| {
+            let start = Instant::now();
+            f(black_box(k));
+            (*class, start.elapsed().as_nanos() as f64)
+        })
+        .collect();
+
+    let mut sorted: Vec<f64> = timings.iter().map(|(_, t)| *t).collect();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let mut max = 0f64;
+    for percentile in [1.0, 0.99, 0.95, 0.9, 0.8, 0.5] {
+        let cutoff = sorted[((sorted.len() - 1) as f64 * percentile) as usize];
+        let mut welch = Welch::default();
+        for (class, t) in timings.iter().filter(|(_, t)| *t <= cutoff) {
+            welch.push(*class, *t);
+        }
+        max = max.max(welch.t().abs());
+    }
+    max
+}
+
+#[test]
+#[ignore]
+fn dudect_g_mul_ct() {
+    let t = max_t(|k| {
+        black_box(g_mul_ct(k));
+    });
+    println!("g_mul_ct: max |t| = {:.2}", t);
+    assert!(t < T_THRESHOLD, "g_mul_ct leaks timing, |t| = {:.2}", t);
+}
+
+#[test]
+#[ignore]
+fn dudect_scalar_mul_ct() {
+    let p = g_mul(&[0x1234_5678, 0x9abc, 0xdef0, 0x42]);
+    let t = max_t(|k| {
+        black_box(p.scalar_mul_ct(k));
+    });
+    println!("scalar_mul_ct: max |t| = {:.2}", t);
+    assert!(t < T_THRESHOLD, "scalar_mul_ct leaks timing, |t| = {:.2}", t);
+}
+
+/// The variable-time `g_mul` skips zero bytes of the scalar, the harness must
+/// be able to see that.
+#[test]
+#[ignore]
+fn dudect_detects_g_mul() {
+    let t = max_t(|k| {
+        black_box(g_mul(k));
+    });
+    println!("g_mul: max |t| = {:.2}", t);
+    assert!(t > T_THRESHOLD, "g_mul was expected to leak, |t| = {:.2}", t);
+}
@@ -0,0 +1,184 @@
+//! RustCrypto [`digest`] integration.
+//!
+//! [`Sm3Core`] implements the block-level core traits, so [`Sm3Digest`]
+//! (`CoreWrapper<Sm3Core>`) works with `hmac::Hmac`, `hkdf::Hkdf`, `pbkdf2` and
+//! any other code generic over `digest::Digest`. The streaming [`Sm3`] hasher
+//! implements the high-level traits as well.
+
+use core::fmt;
+
+use const_oid::{AssociatedOid, ObjectIdentifier};
+use digest::block_buffer::Eager;
+use digest::consts::{U32, U64};
+use digest::core_api::{
+    AlgorithmName, Block, BlockSizeUser, Buffer, BufferKindUser, CoreWrapper, FixedOutputCore,
+    OutputSizeUser, UpdateCore,
+};
+use digest::{FixedOutput, FixedOutputReset, HashMarker, Output, Reset, Update};
+
+use crate::{cf, Sm3, IV, OID_SM3};
+
+/// SM3 with the RustCrypto `digest` API.
+///
+/// # Example
+/// ```rust
+/// use digest::Digest;
+/// use gm_sm3::Sm3Digest;
+/// fn main(){
+///     let hash = Sm3Digest::digest(b"abc");
+///     assert_eq!("66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0", hex::encode(hash));
+/// }
+/// ```
+pub type Sm3Digest = CoreWrapper<Sm3Core>;
+
+/// Block-level SM3 state used by [`Sm3Digest`].
+#[derive(Clone)]
+pub struct Sm3Core {
+    v: [u32; 8],
+    block_len: u64,
+}
+
+impl HashMarker for Sm3Core {}
+
+impl BlockSizeUser for Sm3Core {
+    type BlockSize = U64;
+}
+
+impl BufferKindUser for Sm3Core {
+    type BufferKind = Eager;
+}
+
+impl OutputSizeUser for Sm3Core {
+    type OutputSize = U32;
+}
+
+impl UpdateCore for Sm3Core {
+    #[inline]
+    fn update_blocks(&mut self, blocks: &[Block<Self>]) {
+        self.block_len = self.block_len.wrapping_add(blocks.len() as u64);
+        for block in blocks {
+            cf(&mut self.v, (*block).into());
+        }
+    }
+}
+
+impl FixedOutputCore for Sm3Core {
+    #[inline]
+    fn finalize_fixed_core(&mut self, buffer: &mut Buffer<Self>, out: &mut Output<Self>) {
+        let bit_len = self
+            .block_len
+            .wrapping_mul(512)
+            .wrapping_add(buffer.get_pos() as u64 * 8);
+        let mut v = self.v;
+        buffer.len64_padding_be(bit_len, |b| cf(&mut v, (*b).into()));
+        for (chunk, v) in out.chunks_exact_mut(4).zip(v.iter()) {
+            chunk.copy_from_slice(&v.to_be_bytes());
+        }
+    }
+}
+
+impl Default for Sm3Core {
+    #[inline]
+    fn default() -> Self {
+        Self { v: IV, block_len: 0 }
+    }
+}
+
+impl Reset for Sm3Core {
+    #[inline]
+    fn reset(&mut self) {
+        *self = Default::default();
+    }
+}
+
+impl AlgorithmName for Sm3Core {
+    fn write_alg_name(f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Sm3")
+    }
+}
+
+impl fmt::Debug for Sm3Core {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Sm3Core { ... }")
+    }
+}
+
+impl AssociatedOid for Sm3Core {
+    const OID: ObjectIdentifier = OID_SM3;
+}
+
+impl HashMarker for Sm3 {}
+
+impl BlockSizeUser for Sm3 {
+    type BlockSize = U64;
+}
+
+impl OutputSizeUser for Sm3 {
+    type OutputSize = U32;
+}
+
+impl Update for Sm3 {
+    fn update(&mut self, data: &[u8]) {
+        Sm3::update(self, data)
+    }
+}
+
+impl FixedOutput for Sm3 {
+    fn finalize_into(mut self, out: &mut Output<Self>) {
+        out.copy_from_slice(&Sm3::finalize_inner(&mut self));
+    }
+}
+
+impl FixedOutputReset for Sm3 {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        out.copy_from_slice(&Sm3::finalize_inner(self));
+        Sm3::reset(self);
+    }
+}
+
+impl Reset for Sm3 {
+    fn reset(&mut self) {
+        Sm3::reset(self)
+    }
+}
+
+impl AssociatedOid for Sm3 {
+    const OID: ObjectIdentifier = OID_SM3;
+}
+
+#[cfg(test)]
+mod test {
+    use ::hmac::{Hmac, Mac, SimpleHmac};
+    use digest::Digest;
+
+    use crate::core_api::Sm3Digest;
+    use crate::{sm3_hash, Sm3};
+
+    #[test]
+    fn test_digest_matches_sm3_hash() {
+        let msg: Vec<u8> = (0..300u32).map(|i| i as u8).collect();
+        for len in [0, 3, 55, 56, 64, 65, 300] {
+            let mut core = Sm3Digest::new();
+            Digest::update(&mut core, &msg[..len]);
+            assert_eq!(&core.finalize()[..], sm3_hash(&msg[..len]));
+
+            let mut hasher = <Sm3 as Digest>::new();
+            Digest::update(&mut hasher, &msg[..len]);
+            assert_eq!(&Digest::finalize(hasher)[..], sm3_hash(&msg[..len]));
+        }
+    }
+
+    #[test]
+    fn test_digest_hmac() {
+        let expected = "bd4a34077888162b210645b8ebf74b9af357303789357a27c7fc457244ebd398";
+        let msg = b"The quick brown fox jumps over the lazy dog";
+
+        let mut mac = Hmac::<Sm3Digest>::new_from_slice(b"key").unwrap();
+        mac.update(msg);
+        assert_eq!(expected, hex::encode(mac.finalize().into_bytes()));
+
+        let mut mac = SimpleHmac::<Sm3>::new_from_slice(b"key").unwrap();
+        mac.update(msg);
+        assert_eq!(expected, hex::encode(mac.finalize().into_bytes()));
+    }
+}
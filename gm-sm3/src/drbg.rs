@@ -0,0 +1,375 @@
+use rand_core::{CryptoRng, OsRng, RngCore};
+
+use crate::{Sm3, Sm3Error};
+
+/// seedlen of Hash_DRBG for a 256-bit hash, in bytes (440 bits)
+const SEED_LEN: usize = 55;
+
+/// Minimum entropy input for the 256-bit security strength
+const MIN_ENTROPY_LEN: usize = 32;
+
+const MIN_NONCE_LEN: usize = 16;
+
+/// Largest request served by one generate call (2^19 bits)
+pub const SM3_DRBG_MAX_REQUEST_LEN: usize = 1 << 16;
+
+/// Reseed interval required by GM/T 0105 for a level 1 DRBG
+pub const SM3_DRBG_RESEED_INTERVAL: u64 = 1 << 20;
+
+/// SM3 Hash_DRBG as defined in GM/T 0105 and NIST SP 800-90A.
+///
+/// [`Sm3Drbg::new`] seeds itself from the operating system and reseeds from it
+/// again after [`SM3_DRBG_RESEED_INTERVAL`] requests, or before every request
+/// when prediction resistance is enabled. [`Sm3Drbg::instantiate`] takes
+/// caller supplied entropy, e.g. from a hardware noise source or for known
+/// answer tests.
+///
+/// # Example
+/// ```rust
+/// use gm_sm3::Sm3Drbg;
+/// use rand_core::RngCore;
+/// fn main(){
+///     let mut drbg = Sm3Drbg::new().unwrap();
+///     let mut key = [0u8; 16];
+///     drbg.fill_bytes(&mut key);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Sm3Drbg {
+    v: [u8; SEED_LEN],
+    c: [u8; SEED_LEN],
+    reseed_counter: u64,
+    reseed_interval: u64,
+    prediction_resistance: bool,
+}
+
+impl Sm3Drbg {
+    /// Instantiate from operating system entropy without prediction resistance.
+    pub fn new() -> Result<Sm3Drbg, Sm3Error> {
+        Self::from_os_rng(false)
+    }
+
+    /// Instantiate from operating system entropy, with the given prediction resistance flag.
+    pub fn from_os_rng(prediction_resistance: bool) -> Result<Sm3Drbg, Sm3Error> {
+        let mut entropy = [0u8; MIN_ENTROPY_LEN];
+        let mut nonce = [0u8; MIN_NONCE_LEN];
+        os_entropy(&mut entropy)?;
+        os_entropy(&mut nonce)?;
+        let drbg = Self::instantiate(&entropy, &nonce, &[], prediction_resistance);
+        wipe(&mut entropy);
+        wipe(&mut nonce);
+        drbg
+    }
+
+    /// Instantiate_function: `entropy` must hold at least 32 bytes and `nonce` at least 16 bytes.
+    pub fn instantiate(
+        entropy: &[u8],
+        nonce: &[u8],
+        personalization: &[u8],
+        prediction_resistance: bool,
+    ) -> Result<Sm3Drbg, Sm3Error> {
+        if entropy.len() < MIN_ENTROPY_LEN || nonce.len() < MIN_NONCE_LEN {
+            return Err(Sm3Error::ErrorEntropy);
+        }
+        let mut v = hash_df(&[entropy, nonce, personalization]);
+        let mut c = hash_df(&[&[0x00], &v]);
+        let drbg = Sm3Drbg {
+            v,
+            c,
+            reseed_counter: 1,
+            reseed_interval: SM3_DRBG_RESEED_INTERVAL,
+            prediction_resistance,
+        };
+        wipe(&mut v);
+        wipe(&mut c);
+        Ok(drbg)
+    }
+
+    /// Lower the number of requests served between automatic reseeds.
+    pub fn set_reseed_interval(&mut self, interval: u64) {
+        self.reseed_interval = interval.clamp(1, SM3_DRBG_RESEED_INTERVAL);
+    }
+
+    pub fn prediction_resistance(&self) -> bool {
+        self.prediction_resistance
+    }
+
+    /// Reseed_function with caller supplied entropy of at least 32 bytes.
+    pub fn reseed(&mut self, entropy: &[u8], additional: &[u8]) -> Result<(), Sm3Error> {
+        if entropy.len() < MIN_ENTROPY_LEN {
+            return Err(Sm3Error::ErrorEntropy);
+        }
+        let mut v = hash_df(&[&[0x01], &self.v, entropy, additional]);
+        self.c = hash_df(&[&[0x00], &v]);
+        self.v = v;
+        wipe(&mut v);
+        self.reseed_counter = 1;
+        Ok(())
+    }
+
+    /// Generate_function. Reseeds from the operating system first when prediction
+    /// resistance is enabled or the reseed interval has been reached.
+    pub fn generate(&mut self, out: &mut [u8], additional: &[u8]) -> Result<(), Sm3Error> {
+        if self.prediction_resistance || self.reseed_counter > self.reseed_interval {
+            let mut entropy = [0u8; MIN_ENTROPY_LEN];
+            os_entropy(&mut entropy)?;
+            let result = self.generate_with_entropy(out, &entropy, additional);
+            wipe(&mut entropy);
+            return result;
+        }
+        self.generate_raw(out, additional)
+    }
+
+    /// Generate_function with prediction resistance: reseed with the given fresh
+    /// `entropy` and `additional` input, then generate.
+    pub fn generate_with_entropy(
+        &mut self,
+        out: &mut [u8],
+        entropy: &[u8],
+        additional: &[u8],
+    ) -> Result<(), Sm3Error> {
+        if out.len() > SM3_DRBG_MAX_REQUEST_LEN {
+            return Err(Sm3Error::ErrorOutputLen);
+        }
+        self.reseed(entropy, additional)?;
+        self.generate_raw(out, &[])
+    }
+
+    fn generate_raw(&mut self, out: &mut [u8], additional: &[u8]) -> Result<(), Sm3Error> {
+        if out.len() > SM3_DRBG_MAX_REQUEST_LEN {
+            return Err(Sm3Error::ErrorOutputLen);
+        }
+        if self.reseed_counter > self.reseed_interval {
+            return Err(Sm3Error::ErrorReseedRequired);
+        }
+
+        // w = Hash(0x02 || V || additional_input), V = (V + w) mod 2^seedlen
+        if !additional.is_empty() {
+            let mut hasher = Sm3::new();
+            hasher.update(&[0x02]);
+            hasher.update(&self.v);
+            hasher.update(additional);
+            add_into(&mut self.v, &hasher.finalize());
+        }
+
+        // Hashgen
+        let mut data = self.v;
+        for chunk in out.chunks_mut(32) {
+            let mut hasher = Sm3::new();
+            hasher.update(&data);
+            chunk.copy_from_slice(&hasher.finalize()[..chunk.len()]);
+            add_into(&mut data, &[0x01]);
+        }
+        wipe(&mut data);
+
+        // V = (V + H + C + reseed_counter) mod 2^seedlen, H = Hash(0x03 || V)
+        let mut hasher = Sm3::new();
+        hasher.update(&[0x03]);
+        hasher.update(&self.v);
+        let h = hasher.finalize();
+        let mut c = self.c;
+        add_into(&mut self.v, &h);
+        add_into(&mut self.v, &c);
+        wipe(&mut c);
+        add_into(&mut self.v, &self.reseed_counter.to_be_bytes());
+        self.reseed_counter += 1;
+        Ok(())
+    }
+}
+
+impl RngCore for Sm3Drbg {
+    fn next_u32(&mut self) -> u32 {
+        let mut buf = [0u8; 4];
+        self.fill_bytes(&mut buf);
+        u32::from_be_bytes(buf)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut buf = [0u8; 8];
+        self.fill_bytes(&mut buf);
+        u64::from_be_bytes(buf)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest).unwrap()
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        for chunk in dest.chunks_mut(SM3_DRBG_MAX_REQUEST_LEN) {
+            self.generate(chunk, &[]).map_err(rand_core::Error::new)?;
+        }
+        Ok(())
+    }
+}
+
+impl CryptoRng for Sm3Drbg {}
+
+/// Wipes the working state `V` and `C`. A wiped DRBG has to be reseeded before
+/// it generates again, which [`Sm3Drbg::generate`] does from the operating system.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Sm3Drbg {
+    fn zeroize(&mut self) {
+        self.v.zeroize();
+        self.c.zeroize();
+        self.reseed_counter = u64::MAX;
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Sm3Drbg {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for Sm3Drbg {}
+
+/// Clears a temporary copy of the secret state when the `zeroize` feature is on.
+fn wipe(buf: &mut [u8]) {
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(buf);
+    #[cfg(not(feature = "zeroize"))]
+    let _ = buf;
+}
+
+fn os_entropy(buf: &mut [u8]) -> Result<(), Sm3Error> {
+    OsRng
+        .try_fill_bytes(buf)
+        .map_err(|_| Sm3Error::ErrorEntropy)
+}
+
+/// Hash_df(input_string, seedlen)
+fn hash_df(input: &[&[u8]]) -> [u8; SEED_LEN] {
+    let bits = (SEED_LEN as u32) * 8;
+    let mut out = [0u8; SEED_LEN];
+    for (counter, chunk) in out.chunks_mut(32).enumerate() {
+        let mut hasher = Sm3::new();
+        hasher.update(&[counter as u8 + 1]);
+        hasher.update(&bits.to_be_bytes());
+        for part in input {
+            hasher.update(part);
+        }
+        chunk.copy_from_slice(&hasher.finalize()[..chunk.len()]);
+    }
+    out
+}
+
+/// a = (a + b) mod 2^(8 * a.len()), both big endian
+fn add_into(a: &mut [u8; SEED_LEN], b: &[u8]) {
+    let mut carry = 0u16;
+    for i in 0..SEED_LEN {
+        let ai = SEED_LEN - 1 - i;
+        let bi = if i < b.len() {
+            b[b.len() - 1 - i] as u16
+        } else {
+            0
+        };
+        let sum = a[ai] as u16 + bi + carry;
+        a[ai] = sum as u8;
+        carry = sum >> 8;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand_core::RngCore;
+
+    use crate::drbg::Sm3Drbg;
+
+    const ENTROPY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+    const NONCE: &str = "20212223242526272829202b2c2d2e2f";
+
+    // expected outputs computed with an independent Hash_DRBG implementation over SM3
+    #[test]
+    fn test_drbg_kat() {
+        let entropy = hex::decode(ENTROPY).unwrap();
+        let nonce = hex::decode(NONCE).unwrap();
+        let mut drbg = Sm3Drbg::instantiate(&entropy, &nonce, b"gm-rs", false).unwrap();
+        let mut out = [0u8; 64];
+        drbg.generate(&mut out, &[]).unwrap();
+        drbg.generate(&mut out, &[]).unwrap();
+        assert_eq!(
+            "5b69278bae5cbcc814aa64dcc8d1a79c6fb21ece547272d6ca75cfcf53d7ba27\
+             417811d7d2d974f13d850cc026ef20c0e81aeba02439294269f44b03d2cfd5ca",
+            hex::encode(out)
+        );
+    }
+
+    #[test]
+    fn test_drbg_kat_additional_input_and_reseed() {
+        let entropy = hex::decode(ENTROPY).unwrap();
+        let nonce = hex::decode(NONCE).unwrap();
+        let mut drbg = Sm3Drbg::instantiate(&entropy, &nonce, &[], false).unwrap();
+        let mut out = [0u8; 40];
+        drbg.generate(&mut out, b"additional 1").unwrap();
+        drbg.reseed(&[0x80; 32], b"reseed").unwrap();
+        drbg.generate(&mut out, b"additional 2").unwrap();
+        assert_eq!(
+            "347d00fa629fb9472837fd72572517f97cdaf7964fe4bf391e2181f24bf2fc453195ca2df1afc339",
+            hex::encode(out)
+        );
+    }
+
+    #[test]
+    fn test_drbg_kat_prediction_resistance() {
+        let entropy = hex::decode(ENTROPY).unwrap();
+        let nonce = hex::decode(NONCE).unwrap();
+        let mut drbg = Sm3Drbg::instantiate(&entropy, &nonce, &[], true).unwrap();
+        let mut out = [0u8; 32];
+        drbg.generate_with_entropy(&mut out, &[0x11; 32], b"first")
+            .unwrap();
+        drbg.generate_with_entropy(&mut out, &[0x22; 32], &[])
+            .unwrap();
+        assert_eq!(
+            "8bc6dd64326da9fbbad64892df3a6040c10b04f11aae369ce8860c982f9a6476",
+            hex::encode(out)
+        );
+    }
+
+    #[test]
+    fn test_drbg_errors() {
+        let entropy = hex::decode(ENTROPY).unwrap();
+        let nonce = hex::decode(NONCE).unwrap();
+        assert!(Sm3Drbg::instantiate(&entropy[..31], &nonce, &[], false).is_err());
+        assert!(Sm3Drbg::instantiate(&entropy, &nonce[..15], &[], false).is_err());
+
+        let mut drbg = Sm3Drbg::instantiate(&entropy, &nonce, &[], false).unwrap();
+        let mut big = vec![0u8; (1 << 16) + 1];
+        assert!(drbg.generate(&mut big, &[]).is_err());
+        assert!(drbg.reseed(&[0; 16], &[]).is_err());
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_drbg_zeroize() {
+        use zeroize::Zeroize;
+
+        let entropy = hex::decode(ENTROPY).unwrap();
+        let nonce = hex::decode(NONCE).unwrap();
+        let mut drbg = Sm3Drbg::instantiate(&entropy, &nonce, &[], false).unwrap();
+        drbg.zeroize();
+        assert_eq!([0u8; 55], drbg.v);
+        assert_eq!([0u8; 55], drbg.c);
+        let mut out = [0u8; 32];
+        assert!(drbg.generate_raw(&mut out, &[]).is_err());
+        drbg.reseed(&[0x80; 32], &[]).unwrap();
+        drbg.generate(&mut out, &[]).unwrap();
+    }
+
+    #[test]
+    fn test_drbg_rng_core() {
+        let mut drbg = Sm3Drbg::new().unwrap();
+        drbg.set_reseed_interval(2);
+        let mut a = [0u8; 100];
+        let mut b = [0u8; 100];
+        drbg.fill_bytes(&mut a);
+        drbg.fill_bytes(&mut b);
+        drbg.fill_bytes(&mut b);
+        assert_ne!(a, b);
+
+        let mut drbg = Sm3Drbg::from_os_rng(true).unwrap();
+        assert!(drbg.prediction_resistance());
+        assert_ne!(drbg.next_u64(), drbg.next_u64());
+    }
+}
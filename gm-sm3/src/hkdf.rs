@@ -0,0 +1,100 @@
+use crate::hmac::HmacSm3;
+use crate::Sm3Error;
+
+/// HKDF-Extract (RFC 5869) with HMAC-SM3.
+///
+/// An empty `salt` is equivalent to 32 zero bytes.
+pub fn hkdf_sm3_extract(salt: &[u8], ikm: &[u8]) -> [u8; 32] {
+    let mut mac = HmacSm3::new(salt);
+    mac.update(ikm);
+    mac.finalize()
+}
+
+/// HKDF-Expand (RFC 5869) with HMAC-SM3.
+///
+/// `len` must not exceed 255 * 32 bytes.
+pub fn hkdf_sm3_expand(prk: &[u8; 32], info: &[u8], len: usize) -> Result<Vec<u8>, Sm3Error> {
+    if len > 255 * 32 {
+        return Err(Sm3Error::ErrorOutputLen);
+    }
+    let keyed = HmacSm3::new(prk);
+    let mut okm = Vec::with_capacity(len);
+    let mut t: [u8; 32] = [0; 32];
+    let mut counter = 1u8;
+    while okm.len() < len {
+        let mut mac = keyed.clone();
+        if counter > 1 {
+            mac.update(&t);
+        }
+        mac.update(info);
+        mac.update(&[counter]);
+        t = mac.finalize();
+        let n = (len - okm.len()).min(32);
+        okm.extend_from_slice(&t[..n]);
+        counter = counter.wrapping_add(1);
+    }
+    Ok(okm)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hkdf::{hkdf_sm3_expand, hkdf_sm3_extract};
+
+    // RFC 5869 A.1/A.2/A.3 inputs, outputs cross-checked with GmSSL/OpenSSL HKDF-SM3
+    #[test]
+    fn test_hkdf_basic() {
+        let ikm = [0x0b; 22];
+        let salt: Vec<u8> = (0x00..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+        let prk = hkdf_sm3_extract(&salt, &ikm);
+        assert_eq!(
+            "e0d6f7b0bd056327b7659f1f39ad850561fbcf4fb10fb58e88eafa55cf7cd01e",
+            hex::encode(prk)
+        );
+        let okm = hkdf_sm3_expand(&prk, &info, 42).unwrap();
+        assert_eq!(
+            "c69fe91b7aaee2dd5718d72dcaee0cce93f1b8e41f792da51261b6a517e68b36ed2c595572b01dfa359b",
+            hex::encode(okm)
+        );
+    }
+
+    #[test]
+    fn test_hkdf_long() {
+        let ikm: Vec<u8> = (0x00..=0x4f).collect();
+        let salt: Vec<u8> = (0x60..=0xaf).collect();
+        let info: Vec<u8> = (0xb0..=0xff).collect();
+        let prk = hkdf_sm3_extract(&salt, &ikm);
+        assert_eq!(
+            "1a43a7fedb2d111eb33babd0d256c272aa3262cdb12e6b43d4321ae8888485d5",
+            hex::encode(prk)
+        );
+        let okm = hkdf_sm3_expand(&prk, &info, 82).unwrap();
+        assert_eq!(
+            "c1226236bbdefa7921f9febe27b864f33e449201b436d8844ea53f58170dd6426defbd22ed1f3c5960f35523e62e3b6c0d657f2c61893436f539013199bfaef25aafd1e7726ede927623a9f5cbb8885c7e5d",
+            hex::encode(okm)
+        );
+    }
+
+    #[test]
+    fn test_hkdf_empty_salt_and_info() {
+        let ikm = [0x0b; 22];
+        let prk = hkdf_sm3_extract(&[], &ikm);
+        assert_eq!(prk, hkdf_sm3_extract(&[0; 32], &ikm));
+        assert_eq!(
+            "004fc37143377d072d74e82ff480e8d7937ec607411bc1ec65dd34401871ff9c",
+            hex::encode(prk)
+        );
+        let okm = hkdf_sm3_expand(&prk, &[], 42).unwrap();
+        assert_eq!(
+            "c8c91a38ae2fb3b023a7c38ce9f0748f28230d59b6b950ba3ba949bf0d713a5774815778801741cb2034",
+            hex::encode(okm)
+        );
+    }
+
+    #[test]
+    fn test_hkdf_output_len() {
+        let prk = [0x11; 32];
+        assert_eq!(255 * 32, hkdf_sm3_expand(&prk, b"", 255 * 32).unwrap().len());
+        assert!(hkdf_sm3_expand(&prk, b"", 255 * 32 + 1).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{sm3_hash, Sm3, Sm3Error};
+
+const BLOCK_SIZE: usize = 64;
+
+/// HMAC-SM3 as specified by GM/T 0042 and RFC 2104.
+///
+/// # Example
+/// ```rust
+/// use gm_sm3::HmacSm3;
+/// fn main(){
+///     let mut mac = HmacSm3::new(b"key");
+///     mac.update(b"The quick brown fox ");
+///     mac.update(b"jumps over the lazy dog");
+///     let r = hex::encode(mac.finalize());
+///     assert_eq!("bd4a34077888162b210645b8ebf74b9af357303789357a27c7fc457244ebd398", r);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct HmacSm3 {
+    inner: Sm3,
+    outer: Sm3,
+}
+
+impl HmacSm3 {
+    /// Keys longer than the 64-byte block size are hashed first, shorter keys are zero padded.
+    pub fn new(key: &[u8]) -> HmacSm3 {
+        let mut key_block = [0u8; BLOCK_SIZE];
+        if key.len() > BLOCK_SIZE {
+            key_block[..32].copy_from_slice(&sm3_hash(key));
+        } else {
+            key_block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut ipad = [0x36u8; BLOCK_SIZE];
+        let mut opad = [0x5cu8; BLOCK_SIZE];
+        for i in 0..BLOCK_SIZE {
+            ipad[i] ^= key_block[i];
+            opad[i] ^= key_block[i];
+        }
+
+        let mut inner = Sm3::new();
+        inner.update(&ipad);
+        let mut outer = Sm3::new();
+        outer.update(&opad);
+        HmacSm3 { inner, outer }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Return the 32-byte tag: H((K ^ opad) || H((K ^ ipad) || message))
+    pub fn finalize(self) -> [u8; 32] {
+        let inner_result = self.inner.finalize();
+        let mut outer = self.outer;
+        outer.update(&inner_result);
+        outer.finalize()
+    }
+
+    /// Compare the tag with `expected` in constant time.
+    pub fn verify(self, expected: &[u8]) -> Result<(), Sm3Error> {
+        let tag = self.finalize();
+        if expected.len() != tag.len() {
+            return Err(Sm3Error::InvalidMac);
+        }
+        let mut diff = 0u8;
+        for (a, b) in tag.iter().zip(expected.iter()) {
+            diff |= a ^ b;
+        }
+        if diff == 0 {
+            Ok(())
+        } else {
+            Err(Sm3Error::InvalidMac)
+        }
+    }
+}
+
+/// One-shot HMAC-SM3
+pub fn hmac_sm3(key: &[u8], msg: &[u8]) -> [u8; 32] {
+    let mut mac = HmacSm3::new(key);
+    mac.update(msg);
+    mac.finalize()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hmac::{hmac_sm3, HmacSm3};
+
+    // RFC 4231 inputs, expected tags cross-checked with GmSSL/OpenSSL HMAC-SM3
+    #[test]
+    fn test_hmac_vectors() {
+        let cases: [(Vec<u8>, Vec<u8>, &str); 6] = [
+            (
+                vec![0x0b; 20],
+                b"Hi There".to_vec(),
+                "51b00d1fb49832bfb01c3ce27848e59f871d9ba938dc563b338ca964755cce70",
+            ),
+            (
+                b"Jefe".to_vec(),
+                b"what do ya want for nothing?".to_vec(),
+                "2e87f1d16862e6d964b50a5200bf2b10b764faa9680a296a2405f24bec39f882",
+            ),
+            (
+                vec![0xaa; 20],
+                vec![0xdd; 50],
+                "dd9421e1c725bdf52ec1aa34edadb3c97f5951a83a2fa93f73a7902bc1dcc777",
+            ),
+            (
+                (1..=25).collect(),
+                vec![0xcd; 50],
+                "b57c79be03472aeb8cada581dea332cb2ba83d19cb1b052dd07194def75fb8cd",
+            ),
+            (
+                vec![0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                "b4fd844e13342002f0b2e0690ea7741f1497d993a70494cea601e657bedf67a0",
+            ),
+            (
+                vec![0xaa; 131],
+                b"This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.".to_vec(),
+                "5acbdeb0c8c1ef3a99088fe51c0a1d5f4e1c175935f016aee74eb8056db18acb",
+            ),
+        ];
+        for (key, msg, expected) in cases.iter() {
+            assert_eq!(*expected, hex::encode(hmac_sm3(key, msg)));
+        }
+    }
+
+    #[test]
+    fn test_hmac_verify() {
+        let tag = hmac_sm3(b"Jefe", b"what do ya want for nothing?");
+        let mut mac = HmacSm3::new(b"Jefe");
+        mac.update(b"what do ya want ");
+        mac.update(b"for nothing?");
+        assert!(mac.clone().verify(&tag).is_ok());
+        assert!(mac.clone().verify(&tag[..31]).is_err());
+
+        let mut bad = tag;
+        bad[31] ^= 1;
+        assert!(mac.verify(&bad).is_err());
+    }
+}
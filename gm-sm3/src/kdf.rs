@@ -0,0 +1,141 @@
+use crate::{Sm3, Sm3Error};
+
+/// The largest output the 32-bit counter of the GM/T KDF can produce.
+const MAX_OUTPUT_LEN: u64 = (u32::MAX as u64) * 32;
+
+/// Key derivation function defined in GM/T 0003 and GM/T 0044:
+/// `K = Hv(Z || ct) || Hv(Z || ct + 1) || ...` truncated to `klen` bytes.
+///
+/// Panics if `klen` exceeds `(2^32 - 1) * 32` bytes.
+///
+/// # Example
+/// ```rust
+/// use gm_sm3::sm3_kdf;
+/// fn main(){
+///     let k = sm3_kdf(b"shared secret", 16);
+///     assert_eq!(16, k.len());
+/// }
+/// ```
+pub fn sm3_kdf(z: &[u8], klen: usize) -> Vec<u8> {
+    let mut k = vec![0; klen];
+    Sm3KdfReader::new(z).read(&mut k).unwrap();
+    k
+}
+
+/// Incremental form of [`sm3_kdf`] that produces key stream on demand.
+///
+/// `Z` is absorbed once, so each further 32-byte block costs a single SM3
+/// compression over the counter. Reading `a` then `b` bytes yields the same
+/// output as `sm3_kdf(z, a + b)`.
+#[derive(Clone)]
+pub struct Sm3KdfReader {
+    prefix: Sm3,
+    ct: u32,
+    block: [u8; 32],
+    pos: usize,
+    remaining: u64,
+}
+
+impl Sm3KdfReader {
+    pub fn new(z: &[u8]) -> Sm3KdfReader {
+        let mut prefix = Sm3::new();
+        prefix.update(z);
+        Sm3KdfReader {
+            prefix,
+            ct: 1,
+            block: [0; 32],
+            pos: 32,
+            remaining: MAX_OUTPUT_LEN,
+        }
+    }
+
+    /// Fill `out` with the next bytes of key stream.
+    pub fn read(&mut self, out: &mut [u8]) -> Result<(), Sm3Error> {
+        if out.len() as u64 > self.remaining {
+            return Err(Sm3Error::ErrorOutputLen);
+        }
+        self.remaining -= out.len() as u64;
+
+        let mut filled = 0;
+        while filled < out.len() {
+            if self.pos == 32 {
+                let mut hasher = self.prefix.clone();
+                hasher.update(&self.ct.to_be_bytes());
+                self.block = hasher.finalize();
+                self.ct = self.ct.wrapping_add(1);
+                self.pos = 0;
+            }
+            let n = (32 - self.pos).min(out.len() - filled);
+            out[filled..filled + n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+            self.pos += n;
+            filled += n;
+        }
+        Ok(())
+    }
+}
+
+/// Wipes the absorbed `Z` and the buffered key stream. Further reads fail
+/// instead of returning a stream derived from the wiped state.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Sm3KdfReader {
+    fn zeroize(&mut self) {
+        self.prefix.zeroize();
+        self.block.zeroize();
+        self.remaining = 0;
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Sm3KdfReader {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for Sm3KdfReader {}
+
+#[cfg(test)]
+mod test {
+    use crate::kdf::{sm3_kdf, Sm3KdfReader};
+    use crate::sm3_hash;
+
+    #[test]
+    fn test_kdf_blocks() {
+        let z = b"abc";
+        let k = sm3_kdf(z, 70);
+        assert_eq!(70, k.len());
+        assert_eq!(&k[..32], &sm3_hash(b"abc\x00\x00\x00\x01"));
+        assert_eq!(&k[32..64], &sm3_hash(b"abc\x00\x00\x00\x02"));
+        assert_eq!(&k[64..], &sm3_hash(b"abc\x00\x00\x00\x03")[..6]);
+        assert!(sm3_kdf(z, 0).is_empty());
+    }
+
+    #[test]
+    fn test_kdf_reader_matches_one_shot() {
+        let z: Vec<u8> = (0..100u8).collect();
+        let expected = sm3_kdf(&z, 1000);
+        let mut reader = Sm3KdfReader::new(&z);
+        let mut out = vec![0u8; 1000];
+        let mut pos = 0;
+        for n in [1, 31, 32, 33, 0, 64, 100, 739] {
+            reader.read(&mut out[pos..pos + n]).unwrap();
+            pos += n;
+        }
+        assert_eq!(expected, out);
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_kdf_reader_zeroize() {
+        use zeroize::Zeroize;
+
+        let mut reader = Sm3KdfReader::new(b"shared secret");
+        reader.read(&mut [0u8; 40]).unwrap();
+        reader.zeroize();
+        assert_eq!([0u8; 32], reader.block);
+        assert_eq!([0u8; 64], reader.prefix.buf);
+        assert_eq!([0u32; 8], reader.prefix.v);
+        assert!(reader.read(&mut [0u8; 1]).is_err());
+    }
+}
@@ -3,11 +3,35 @@
 use std::fmt::{Display, Formatter};
 use const_oid::ObjectIdentifier;
 
+pub use digest;
+
+pub use crate::core_api::{Sm3Core, Sm3Digest};
+pub use crate::drbg::{Sm3Drbg, SM3_DRBG_MAX_REQUEST_LEN, SM3_DRBG_RESEED_INTERVAL};
+pub use crate::hkdf::{hkdf_sm3_expand, hkdf_sm3_extract};
+pub use crate::hmac::{hmac_sm3, HmacSm3};
+pub use crate::kdf::{sm3_kdf, Sm3KdfReader};
+pub use crate::multi::sm3_hash_many;
+pub use crate::pbkdf2::pbkdf2_hmac_sm3;
+
+mod core_api;
+mod drbg;
+mod hkdf;
+mod hmac;
+mod kdf;
+mod multi;
+mod pbkdf2;
+
 
 pub const OID_SM3: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.156.10197.1.401");
 
 pub enum Sm3Error {
+    #[deprecated(note = "the streaming hasher accepts messages of any length")]
     ErrorMsgLen,
+    InvalidMac,
+    ErrorOutputLen,
+    ErrorIterations,
+    ErrorEntropy,
+    ErrorReseedRequired,
 }
 
 impl std::fmt::Debug for Sm3Error {
@@ -17,22 +41,36 @@ impl std::fmt::Debug for Sm3Error {
 }
 
 impl From<Sm3Error> for &str {
+    #[allow(deprecated)]
     fn from(e: Sm3Error) -> Self {
         match e {
             Sm3Error::ErrorMsgLen => "SM3 Pad error: error msg len",
+            Sm3Error::InvalidMac => "HMAC-SM3 verify error: mac mismatch",
+            Sm3Error::ErrorOutputLen => "SM3 error: the requested output is too long",
+            Sm3Error::ErrorIterations => "PBKDF2-SM3 error: rounds must be positive",
+            Sm3Error::ErrorEntropy => "SM3 DRBG error: entropy input is unavailable or too short",
+            Sm3Error::ErrorReseedRequired => "SM3 DRBG error: reseed required",
         }
     }
 }
 
 impl Display for Sm3Error {
+    #[allow(deprecated)]
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let err_msg = match self {
             Sm3Error::ErrorMsgLen => "SM3 Pad error: error msg len",
+            Sm3Error::InvalidMac => "HMAC-SM3 verify error: mac mismatch",
+            Sm3Error::ErrorOutputLen => "SM3 error: the requested output is too long",
+            Sm3Error::ErrorIterations => "PBKDF2-SM3 error: rounds must be positive",
+            Sm3Error::ErrorEntropy => "SM3 DRBG error: entropy input is unavailable or too short",
+            Sm3Error::ErrorReseedRequired => "SM3 DRBG error: reseed required",
         };
         write!(f, "{}", err_msg)
     }
 }
 
+impl std::error::Error for Sm3Error {}
+
 // 0 ≤ j ≤ 15
 pub(crate) const T00: u32 = 0x79cc4519;
 
@@ -92,28 +130,129 @@ fn t(j: usize) -> u32 {
 /// ```
 ///
 pub fn sm3_hash(msg: &[u8]) -> [u8; 32] {
-    let msg = pad(msg).unwrap();
-    let len = msg.len();
-    let mut b_i: [u8; 64] = [0; 64];
-    let mut count_group: usize = 0;
-    let mut v_i = IV;
-    while count_group * 64 != len {
-        for i in (count_group * 64)..(count_group * 64 + 64) {
-            b_i[i - count_group * 64] = msg[i];
+    let mut hasher = Sm3::new();
+    hasher.update(msg);
+    hasher.finalize()
+}
+
+/// Incremental SM3 hasher.
+///
+/// Keeps only the 8-word chaining value and one 64-byte block of pending input,
+/// so arbitrarily large messages can be hashed without buffering them.
+///
+/// # Example
+/// ```rust
+/// use gm_sm3::Sm3;
+/// fn main(){
+///     let mut hasher = Sm3::new();
+///     hasher.update(b"a");
+///     hasher.update(b"bc");
+///     let r = hex::encode(hasher.finalize());
+///     assert_eq!("66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0", r);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Sm3 {
+    v: [u32; 8],
+    buf: [u8; 64],
+    buf_len: usize,
+    msg_len: u64,
+}
+
+impl Default for Sm3 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sm3 {
+    pub fn new() -> Sm3 {
+        Sm3 {
+            v: IV,
+            buf: [0; 64],
+            buf_len: 0,
+            msg_len: 0,
+        }
+    }
+
+    /// Absorb `data` into the hash state.
+    pub fn update(&mut self, data: &[u8]) {
+        self.msg_len = self.msg_len.wrapping_add(data.len() as u64);
+        let mut data = data;
+
+        // fill up the pending block first
+        if self.buf_len > 0 {
+            let n = (64 - self.buf_len).min(data.len());
+            self.buf[self.buf_len..self.buf_len + n].copy_from_slice(&data[..n]);
+            self.buf_len += n;
+            data = &data[n..];
+            if self.buf_len < 64 {
+                return;
+            }
+            cf(&mut self.v, self.buf);
+            self.buf_len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            cf(&mut self.v, block.try_into().unwrap());
+        }
+        let rest = blocks.remainder();
+        self.buf[..rest.len()].copy_from_slice(rest);
+        self.buf_len = rest.len();
+    }
+
+    /// Pad the pending input and return the 32-byte digest.
+    pub fn finalize(mut self) -> [u8; 32] {
+        self.finalize_inner()
+    }
+
+    /// Clear the state so the hasher can be reused for a new message.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    fn finalize_inner(&mut self) -> [u8; 32] {
+        let bit_length = self.msg_len.wrapping_shl(3);
+        self.buf[self.buf_len] = 0x80;
+        self.buf[self.buf_len + 1..].fill(0);
+        if self.buf_len >= 56 {
+            cf(&mut self.v, self.buf);
+            self.buf = [0; 64];
+        }
+        self.buf[56..].copy_from_slice(&bit_length.to_be_bytes());
+        cf(&mut self.v, self.buf);
+
+        let mut output: [u8; 32] = [0; 32];
+        for i in 0..8 {
+            output[i * 4..i * 4 + 4].copy_from_slice(&self.v[i].to_be_bytes());
         }
-        cf(&mut v_i, b_i);
-        count_group += 1;
+        output
     }
-    let mut output: [u8; 32] = [0; 32];
-    for i in 0..8 {
-        output[i * 4] = (v_i[i] >> 24) as u8;
-        output[i * 4 + 1] = (v_i[i] >> 16) as u8;
-        output[i * 4 + 2] = (v_i[i] >> 8) as u8;
-        output[i * 4 + 3] = v_i[i] as u8;
+}
+
+/// Wipes the chaining value and the buffered input, which may hold key
+/// material absorbed by a KDF or MAC.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Sm3 {
+    fn zeroize(&mut self) {
+        self.v.zeroize();
+        self.buf.zeroize();
+        self.buf_len.zeroize();
+        self.msg_len.zeroize();
     }
-    output
 }
 
+#[cfg(feature = "zeroize")]
+impl Drop for Sm3 {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for Sm3 {}
+
 fn cf(v_i: &mut [u32; 8], b_i: [u8; 64]) {
     // expend msg
     let mut w: [u32; 68] = [0; 68];
@@ -188,28 +327,6 @@ fn cf(v_i: &mut [u32; 8], b_i: [u8; 64]) {
     v_i[7] ^= h;
 }
 
-fn pad(msg: &[u8]) -> Result<Vec<u8>, Sm3Error> {
-    let bit_length = (msg.len() << 3) as u64;
-    let mut msg = msg.to_vec();
-    msg.push(0x80);
-    let blocksize = 64;
-    while msg.len() % blocksize != 56 {
-        msg.push(0x00);
-    }
-    msg.push((bit_length >> 56 & 0xff) as u8);
-    msg.push((bit_length >> 48 & 0xff) as u8);
-    msg.push((bit_length >> 40 & 0xff) as u8);
-    msg.push((bit_length >> 32 & 0xff) as u8);
-    msg.push((bit_length >> 24 & 0xff) as u8);
-    msg.push((bit_length >> 16 & 0xff) as u8);
-    msg.push((bit_length >> 8 & 0xff) as u8);
-    msg.push((bit_length & 0xff) as u8);
-    if msg.len() % 64 != 0 {
-        return Err(Sm3Error::ErrorMsgLen);
-    }
-    Ok(msg)
-}
-
 #[cfg(test)]
 mod test {
     use crate::*;
@@ -233,4 +350,40 @@ mod test {
             r
         );
     }
+
+    #[test]
+    fn test_hasher_split_update() {
+        let msg: Vec<u8> = (0..1000u32).map(|i| (i * 7 + 3) as u8).collect();
+        for split in [0, 1, 55, 56, 63, 64, 65, 127, 128, 500, 1000] {
+            let mut hasher = Sm3::new();
+            hasher.update(&msg[..split]);
+            hasher.update(&msg[split..]);
+            assert_eq!(hasher.finalize(), sm3_hash(&msg));
+        }
+    }
+
+    #[test]
+    fn test_hasher_reset() {
+        let mut hasher = Sm3::new();
+        hasher.update(b"garbage");
+        hasher.reset();
+        hasher.update(b"abc");
+        assert_eq!(
+            "66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0",
+            hex::encode(hasher.finalize())
+        );
+    }
+
+    #[test]
+    fn test_hasher_million_a() {
+        let mut hasher = Sm3::new();
+        let chunk = [b'a'; 1000];
+        for _ in 0..1000 {
+            hasher.update(&chunk);
+        }
+        assert_eq!(
+            "c8aaf89429554029e231941a2acc0ad61ff2a5acd8fadd25847a3a732b3b02c3",
+            hex::encode(hasher.finalize())
+        );
+    }
 }
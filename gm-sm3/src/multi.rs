@@ -0,0 +1,456 @@
+//! Multi-buffer SM3: hashes several independent messages at once by running
+//! one message per SIMD lane through the compression function.
+//!
+//! The backend is chosen at runtime: AVX2 (8 lanes) or SSE2 (4 lanes) on
+//! x86_64, NEON (4 lanes) on aarch64, and the scalar `cf` everywhere else.
+
+use crate::sm3_hash;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::{cf, IV, T00, T16};
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const MAX_LANES: usize = 8;
+
+/// T(j) ≪ (j mod 32), precomputed for every round
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const T_ROT: [u32; 64] = {
+    let mut table = [0u32; 64];
+    let mut j = 0;
+    while j < 64 {
+        let t = if j <= 15 { T00 } else { T16 };
+        table[j] = t.rotate_left(j as u32);
+        j += 1;
+    }
+    table
+};
+
+/// Hash every message in `msgs`, returning the digests in the same order.
+///
+/// # Example
+/// ```rust
+/// use gm_sm3::{sm3_hash, sm3_hash_many};
+/// fn main(){
+///     let msgs: [&[u8]; 3] = [b"abc", b"", b"hello world"];
+///     let hashes = sm3_hash_many(&msgs);
+///     for (msg, hash) in msgs.iter().zip(hashes.iter()) {
+///         assert_eq!(&sm3_hash(msg), hash);
+///     }
+/// }
+/// ```
+pub fn sm3_hash_many(msgs: &[&[u8]]) -> Vec<[u8; 32]> {
+    let mut out = vec![[0u8; 32]; msgs.len()];
+    if !hash_many_simd(msgs, &mut out) {
+        for (msg, hash) in msgs.iter().zip(out.iter_mut()) {
+            *hash = sm3_hash(msg);
+        }
+    }
+    out
+}
+
+#[cfg(target_arch = "x86_64")]
+fn hash_many_simd(msgs: &[&[u8]], out: &mut [[u8; 32]]) -> bool {
+    if std::arch::is_x86_feature_detected!("avx2") {
+        unsafe { x86::hash_many_avx2(msgs, out) };
+    } else {
+        // SSE2 is part of the x86_64 baseline
+        unsafe { x86::hash_many_sse2(msgs, out) };
+    }
+    true
+}
+
+#[cfg(target_arch = "aarch64")]
+fn hash_many_simd(msgs: &[&[u8]], out: &mut [[u8; 32]]) -> bool {
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        unsafe { neon::hash_many_neon(msgs, out) };
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn hash_many_simd(_msgs: &[&[u8]], _out: &mut [[u8; 32]]) -> bool {
+    false
+}
+
+/// A vector of `N` independent u32 lanes.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+trait Lanes: Copy {
+    const N: usize;
+    unsafe fn splat(x: u32) -> Self;
+    unsafe fn load(words: &[u32; MAX_LANES]) -> Self;
+    unsafe fn store(self, words: &mut [u32; MAX_LANES]);
+    unsafe fn add(self, rhs: Self) -> Self;
+    unsafe fn xor(self, rhs: Self) -> Self;
+    unsafe fn and(self, rhs: Self) -> Self;
+    unsafe fn or(self, rhs: Self) -> Self;
+    /// !self & rhs
+    unsafe fn andnot(self, rhs: Self) -> Self;
+    unsafe fn rotl(self, n: u32) -> Self;
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+unsafe fn p0<V: Lanes>(x: V) -> V {
+    x.xor(x.rotl(9)).xor(x.rotl(17))
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+unsafe fn p1<V: Lanes>(x: V) -> V {
+    x.xor(x.rotl(15)).xor(x.rotl(23))
+}
+
+/// The compression function of `cf`, applied to every lane.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+unsafe fn cf_lanes<V: Lanes>(v: &mut [V; 8], b: &[V; 16]) {
+    let mut w = [V::splat(0); 68];
+    w[..16].copy_from_slice(b);
+    for j in 16..68 {
+        let x = w[j - 16].xor(w[j - 9]).xor(w[j - 3].rotl(15));
+        w[j] = p1(x).xor(w[j - 13].rotl(7)).xor(w[j - 6]);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *v;
+    for j in 0..64 {
+        let a12 = a.rotl(12);
+        let ss1 = a12.add(e).add(V::splat(T_ROT[j])).rotl(7);
+        let ss2 = ss1.xor(a12);
+        let (ff, gg) = if j <= 15 {
+            (a.xor(b).xor(c), e.xor(f).xor(g))
+        } else {
+            (
+                a.and(b).or(a.and(c)).or(b.and(c)),
+                e.and(f).or(e.andnot(g)),
+            )
+        };
+        let tt1 = ff.add(d).add(ss2).add(w[j].xor(w[j + 4]));
+        let tt2 = gg.add(h).add(ss1).add(w[j]);
+        d = c;
+        c = b.rotl(9);
+        b = a;
+        a = tt1;
+        h = g;
+        g = f.rotl(19);
+        f = e;
+        e = p0(tt2);
+    }
+    for (v, x) in v.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *v = v.xor(x);
+    }
+}
+
+/// Messages are grouped by length so lanes run for a similar number of blocks;
+/// once the shortest lane of a group is done the rest finish on the scalar path.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+unsafe fn hash_many_lanes<V: Lanes>(msgs: &[&[u8]], out: &mut [[u8; 32]]) {
+    let mut order: Vec<usize> = (0..msgs.len()).collect();
+    order.sort_by_key(|&i| msgs[i].len());
+
+    for group in order.chunks(V::N) {
+        if group.len() < V::N {
+            for &i in group {
+                out[i] = sm3_hash(msgs[i]);
+            }
+            continue;
+        }
+
+        // padded tail of each message: the last partial block, 0x80, zeros and the bit length
+        let mut tails = [[0u8; 128]; MAX_LANES];
+        let mut full = [0usize; MAX_LANES];
+        let mut total = [0usize; MAX_LANES];
+        for (lane, &i) in group.iter().enumerate() {
+            let msg = msgs[i];
+            full[lane] = msg.len() / 64;
+            let rest = &msg[full[lane] * 64..];
+            tails[lane][..rest.len()].copy_from_slice(rest);
+            tails[lane][rest.len()] = 0x80;
+            let tail_blocks = if rest.len() < 56 { 1 } else { 2 };
+            let bit_length = (msg.len() as u64).wrapping_shl(3);
+            tails[lane][tail_blocks * 64 - 8..tail_blocks * 64]
+                .copy_from_slice(&bit_length.to_be_bytes());
+            total[lane] = full[lane] + tail_blocks;
+        }
+        let block = |lane: usize, j: usize| -> [u8; 64] {
+            let src = if j < full[lane] {
+                &msgs[group[lane]][j * 64..j * 64 + 64]
+            } else {
+                &tails[lane][(j - full[lane]) * 64..(j - full[lane]) * 64 + 64]
+            };
+            src.try_into().unwrap()
+        };
+
+        let mut v = [V::splat(0); 8];
+        for (v, iv) in v.iter_mut().zip(IV.iter()) {
+            *v = V::splat(*iv);
+        }
+        let common = total[..V::N].iter().copied().min().unwrap_or(0);
+        for j in 0..common {
+            let mut words = [[0u32; MAX_LANES]; 16];
+            for lane in 0..V::N {
+                let b = block(lane, j);
+                for (k, word) in words.iter_mut().enumerate() {
+                    word[lane] = u32::from_be_bytes(b[k * 4..k * 4 + 4].try_into().unwrap());
+                }
+            }
+            let mut w = [V::splat(0); 16];
+            for (w, word) in w.iter_mut().zip(words.iter()) {
+                *w = V::load(word);
+            }
+            cf_lanes(&mut v, &w);
+        }
+
+        let mut states = [[0u32; MAX_LANES]; 8];
+        for (v, state) in v.iter().zip(states.iter_mut()) {
+            v.store(state);
+        }
+        for (lane, &i) in group.iter().enumerate() {
+            let mut v_i = [0u32; 8];
+            for (v, state) in v_i.iter_mut().zip(states.iter()) {
+                *v = state[lane];
+            }
+            for j in common..total[lane] {
+                cf(&mut v_i, block(lane, j));
+            }
+            for (k, v) in v_i.iter().enumerate() {
+                out[i][k * 4..k * 4 + 4].copy_from_slice(&v.to_be_bytes());
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::{hash_many_lanes, Lanes, MAX_LANES};
+
+    #[derive(Clone, Copy)]
+    pub(super) struct Sse2(__m128i);
+
+    impl Lanes for Sse2 {
+        const N: usize = 4;
+
+        #[inline(always)]
+        unsafe fn splat(x: u32) -> Self {
+            Sse2(_mm_set1_epi32(x as i32))
+        }
+
+        #[inline(always)]
+        unsafe fn load(words: &[u32; MAX_LANES]) -> Self {
+            Sse2(_mm_loadu_si128(words.as_ptr() as *const __m128i))
+        }
+
+        #[inline(always)]
+        unsafe fn store(self, words: &mut [u32; MAX_LANES]) {
+            _mm_storeu_si128(words.as_mut_ptr() as *mut __m128i, self.0)
+        }
+
+        #[inline(always)]
+        unsafe fn add(self, rhs: Self) -> Self {
+            Sse2(_mm_add_epi32(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn xor(self, rhs: Self) -> Self {
+            Sse2(_mm_xor_si128(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn and(self, rhs: Self) -> Self {
+            Sse2(_mm_and_si128(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn or(self, rhs: Self) -> Self {
+            Sse2(_mm_or_si128(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn andnot(self, rhs: Self) -> Self {
+            Sse2(_mm_andnot_si128(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn rotl(self, n: u32) -> Self {
+            let l = _mm_sll_epi32(self.0, _mm_cvtsi32_si128(n as i32));
+            let r = _mm_srl_epi32(self.0, _mm_cvtsi32_si128(32 - n as i32));
+            Sse2(_mm_or_si128(l, r))
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    pub(super) struct Avx2(__m256i);
+
+    impl Lanes for Avx2 {
+        const N: usize = 8;
+
+        #[inline(always)]
+        unsafe fn splat(x: u32) -> Self {
+            Avx2(_mm256_set1_epi32(x as i32))
+        }
+
+        #[inline(always)]
+        unsafe fn load(words: &[u32; MAX_LANES]) -> Self {
+            Avx2(_mm256_loadu_si256(words.as_ptr() as *const __m256i))
+        }
+
+        #[inline(always)]
+        unsafe fn store(self, words: &mut [u32; MAX_LANES]) {
+            _mm256_storeu_si256(words.as_mut_ptr() as *mut __m256i, self.0)
+        }
+
+        #[inline(always)]
+        unsafe fn add(self, rhs: Self) -> Self {
+            Avx2(_mm256_add_epi32(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn xor(self, rhs: Self) -> Self {
+            Avx2(_mm256_xor_si256(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn and(self, rhs: Self) -> Self {
+            Avx2(_mm256_and_si256(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn or(self, rhs: Self) -> Self {
+            Avx2(_mm256_or_si256(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn andnot(self, rhs: Self) -> Self {
+            Avx2(_mm256_andnot_si256(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn rotl(self, n: u32) -> Self {
+            let l = _mm256_sll_epi32(self.0, _mm_cvtsi32_si128(n as i32));
+            let r = _mm256_srl_epi32(self.0, _mm_cvtsi32_si128(32 - n as i32));
+            Avx2(_mm256_or_si256(l, r))
+        }
+    }
+
+    pub(super) unsafe fn hash_many_sse2(msgs: &[&[u8]], out: &mut [[u8; 32]]) {
+        hash_many_lanes::<Sse2>(msgs, out)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn hash_many_avx2(msgs: &[&[u8]], out: &mut [[u8; 32]]) {
+        hash_many_lanes::<Avx2>(msgs, out)
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::{hash_many_lanes, Lanes, MAX_LANES};
+
+    #[derive(Clone, Copy)]
+    pub(super) struct Neon(uint32x4_t);
+
+    impl Lanes for Neon {
+        const N: usize = 4;
+
+        #[inline(always)]
+        unsafe fn splat(x: u32) -> Self {
+            Neon(vdupq_n_u32(x))
+        }
+
+        #[inline(always)]
+        unsafe fn load(words: &[u32; MAX_LANES]) -> Self {
+            Neon(vld1q_u32(words.as_ptr()))
+        }
+
+        #[inline(always)]
+        unsafe fn store(self, words: &mut [u32; MAX_LANES]) {
+            vst1q_u32(words.as_mut_ptr(), self.0)
+        }
+
+        #[inline(always)]
+        unsafe fn add(self, rhs: Self) -> Self {
+            Neon(vaddq_u32(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn xor(self, rhs: Self) -> Self {
+            Neon(veorq_u32(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn and(self, rhs: Self) -> Self {
+            Neon(vandq_u32(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn or(self, rhs: Self) -> Self {
+            Neon(vorrq_u32(self.0, rhs.0))
+        }
+
+        #[inline(always)]
+        unsafe fn andnot(self, rhs: Self) -> Self {
+            // vbic(a, b) = a & !b
+            Neon(vbicq_u32(rhs.0, self.0))
+        }
+
+        #[inline(always)]
+        unsafe fn rotl(self, n: u32) -> Self {
+            // vshl shifts right for negative counts
+            let l = vshlq_u32(self.0, vdupq_n_s32(n as i32));
+            let r = vshlq_u32(self.0, vdupq_n_s32(n as i32 - 32));
+            Neon(vorrq_u32(l, r))
+        }
+    }
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn hash_many_neon(msgs: &[&[u8]], out: &mut [[u8; 32]]) {
+        hash_many_lanes::<Neon>(msgs, out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::multi::sm3_hash_many;
+    use crate::sm3_hash;
+
+    fn messages() -> Vec<Vec<u8>> {
+        (0..67usize)
+            .map(|i| (0..(i * 37) % 300).map(|b| (b * 13 + i) as u8).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_hash_many() {
+        let msgs = messages();
+        let refs: Vec<&[u8]> = msgs.iter().map(|m| m.as_slice()).collect();
+        let hashes = sm3_hash_many(&refs);
+        assert_eq!(msgs.len(), hashes.len());
+        for (msg, hash) in msgs.iter().zip(hashes.iter()) {
+            assert_eq!(&sm3_hash(msg), hash);
+        }
+        assert!(sm3_hash_many(&[]).is_empty());
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_hash_many_x86_backends() {
+        let msgs = messages();
+        let refs: Vec<&[u8]> = msgs.iter().map(|m| m.as_slice()).collect();
+        let expected: Vec<[u8; 32]> = msgs.iter().map(|m| sm3_hash(m)).collect();
+
+        let mut out = vec![[0u8; 32]; refs.len()];
+        unsafe { crate::multi::x86::hash_many_sse2(&refs, &mut out) };
+        assert_eq!(expected, out);
+
+        if std::arch::is_x86_feature_detected!("avx2") {
+            let mut out = vec![[0u8; 32]; refs.len()];
+            unsafe { crate::multi::x86::hash_many_avx2(&refs, &mut out) };
+            assert_eq!(expected, out);
+        }
+    }
+}
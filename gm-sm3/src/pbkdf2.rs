@@ -0,0 +1,98 @@
+use crate::hmac::HmacSm3;
+use crate::Sm3Error;
+
+/// PBKDF2 (RFC 8018) with HMAC-SM3 as the PRF.
+///
+/// # Example
+/// ```rust
+/// use gm_sm3::pbkdf2_hmac_sm3;
+/// fn main(){
+///     let dk = pbkdf2_hmac_sm3(b"password", b"salt", 4096, 32).unwrap();
+///     assert_eq!("b6e8f2074c87432b78f62e5ced980fdff89e86af2f693dab1638e2b3683045dd", hex::encode(dk));
+/// }
+/// ```
+pub fn pbkdf2_hmac_sm3(
+    password: &[u8],
+    salt: &[u8],
+    rounds: u32,
+    dk_len: usize,
+) -> Result<Vec<u8>, Sm3Error> {
+    if rounds == 0 {
+        return Err(Sm3Error::ErrorIterations);
+    }
+    let keyed = HmacSm3::new(password);
+    let mut dk = Vec::with_capacity(dk_len);
+    let mut block_index = 1u32;
+    while dk.len() < dk_len {
+        // U1 = PRF(P, S || INT(i))
+        let mut mac = keyed.clone();
+        mac.update(salt);
+        mac.update(&block_index.to_be_bytes());
+        let mut u = mac.finalize();
+        let mut t = u;
+
+        // Uc = PRF(P, Uc-1), T = U1 ^ U2 ^ ... ^ Uc
+        for _ in 1..rounds {
+            let mut mac = keyed.clone();
+            mac.update(&u);
+            u = mac.finalize();
+            for (t, u) in t.iter_mut().zip(u.iter()) {
+                *t ^= u;
+            }
+        }
+
+        let n = (dk_len - dk.len()).min(32);
+        dk.extend_from_slice(&t[..n]);
+        block_index = block_index.wrapping_add(1);
+    }
+    Ok(dk)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pbkdf2::pbkdf2_hmac_sm3;
+
+    // RFC 6070 inputs, outputs cross-checked with GmSSL/OpenSSL PBKDF2-SM3
+    #[test]
+    fn test_pbkdf2_vectors() {
+        let cases = [
+            (
+                &b"password"[..],
+                &b"salt"[..],
+                1,
+                32,
+                "4612f922a1fdcefaf4312fc6f8f3322b489cbf24f2ea361b44c2bd8fa2c6dcb0",
+            ),
+            (
+                &b"password"[..],
+                &b"salt"[..],
+                2,
+                32,
+                "fee723a2bc966e11dffb66133f4e8df577383c78ade30e3298edbd3e54ed85b7",
+            ),
+            (
+                &b"password"[..],
+                &b"salt"[..],
+                4096,
+                32,
+                "b6e8f2074c87432b78f62e5ced980fdff89e86af2f693dab1638e2b3683045dd",
+            ),
+            (
+                &b"passwordPASSWORDpassword"[..],
+                &b"saltSALTsaltSALTsaltSALTsaltSALTsalt"[..],
+                4096,
+                40,
+                "3b6282ac8519f059e465abff0ea37b0dbfe6c672a76e6b805312d53900db630732ccc1a88fa5512a",
+            ),
+        ];
+        for (password, salt, rounds, dk_len, expected) in cases {
+            let dk = pbkdf2_hmac_sm3(password, salt, rounds, dk_len).unwrap();
+            assert_eq!(expected, hex::encode(dk));
+        }
+    }
+
+    #[test]
+    fn test_pbkdf2_zero_rounds() {
+        assert!(pbkdf2_hmac_sm3(b"password", b"salt", 0, 32).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! SM4 in Galois/Counter Mode (NIST SP 800-38D, RFC 8998).
+
+use crate::{Sm4Cipher, Sm4Error, Sm4Result};
+
+/// Length of the authentication tag appended to the ciphertext.
+pub const SM4_GCM_TAG_LEN: usize = 16;
+
+// R = 11100001 || 0^120
+const GCM_R: u128 = 0xe1 << 120;
+
+/// SM4-GCM authenticated encryption with a 16-byte tag.
+///
+/// # Example
+/// ```rust
+/// use gm_sm4::gcm::Sm4Gcm;
+/// fn main() {
+///     let gcm = Sm4Gcm::new(&[0x42; 16]).unwrap();
+///     let nonce = [0x01; 12];
+///     let sealed = gcm.encrypt(&nonce, b"header", b"hello").unwrap();
+///     assert_eq!(b"hello".to_vec(), gcm.decrypt(&nonce, b"header", &sealed).unwrap());
+///     assert!(gcm.decrypt(&nonce, b"other header", &sealed).is_err());
+/// }
+/// ```
+pub struct Sm4Gcm {
+    cipher: Sm4Cipher,
+    h: u128,
+}
+
+impl Sm4Gcm {
+    pub fn new(key: &[u8]) -> Sm4Result<Sm4Gcm> {
+        let cipher = Sm4Cipher::new(key)?;
+        let h = u128::from_be_bytes(cipher.encrypt(&[0; 16])?.try_into().unwrap());
+        Ok(Sm4Gcm { cipher, h })
+    }
+
+    /// Encrypt `plaintext` and return `ciphertext || tag`.
+    ///
+    /// The nonce must never repeat under the same key; 12 bytes is the
+    /// recommended length, any non-empty length is accepted.
+    pub fn encrypt(&self, nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Sm4Result<Vec<u8>> {
+        let j0 = self.pre_counter(nonce)?;
+        let mut out = self.ctr(j0, plaintext)?;
+        let tag = self.tag(j0, aad, &out)?;
+        out.extend_from_slice(&tag);
+        Ok(out)
+    }
+
+    /// Check the tag of `ciphertext || tag` and decrypt it.
+    pub fn decrypt(&self, nonce: &[u8], aad: &[u8], sealed: &[u8]) -> Sm4Result<Vec<u8>> {
+        if sealed.len() < SM4_GCM_TAG_LEN {
+            return Err(Sm4Error::ErrorDataLen);
+        }
+        let (ciphertext, tag) = sealed.split_at(sealed.len() - SM4_GCM_TAG_LEN);
+        let j0 = self.pre_counter(nonce)?;
+        let expected = self.tag(j0, aad, ciphertext)?;
+        let diff = expected
+            .iter()
+            .zip(tag.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if diff != 0 {
+            return Err(Sm4Error::InvalidTag);
+        }
+        self.ctr(j0, ciphertext)
+    }
+
+    fn pre_counter(&self, nonce: &[u8]) -> Sm4Result<u128> {
+        if nonce.is_empty() {
+            return Err(Sm4Error::ErrorNonceLen);
+        }
+        if nonce.len() == 12 {
+            let mut j0 = [0u8; 16];
+            j0[..12].copy_from_slice(nonce);
+            j0[15] = 1;
+            return Ok(u128::from_be_bytes(j0));
+        }
+        let mut y = ghash_update(self.h, 0, nonce);
+        y = gf_mul(y ^ (nonce.len() as u128 * 8), self.h);
+        Ok(y)
+    }
+
+    /// GCTR starting at `inc32(j0)`.
+    fn ctr(&self, j0: u128, data: &[u8]) -> Sm4Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(data.len() + SM4_GCM_TAG_LEN);
+        let mut counter = j0;
+        for chunk in data.chunks(16) {
+            counter = inc32(counter);
+            let ks = self.cipher.encrypt(&counter.to_be_bytes())?;
+            out.extend(chunk.iter().zip(ks.iter()).map(|(d, k)| d ^ k));
+        }
+        Ok(out)
+    }
+
+    fn tag(&self, j0: u128, aad: &[u8], ciphertext: &[u8]) -> Sm4Result<[u8; 16]> {
+        let mut y = ghash_update(self.h, 0, aad);
+        y = ghash_update(self.h, y, ciphertext);
+        let lengths = ((aad.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+        y = gf_mul(y ^ lengths, self.h);
+        let ek = u128::from_be_bytes(self.cipher.encrypt(&j0.to_be_bytes())?.try_into().unwrap());
+        Ok((y ^ ek).to_be_bytes())
+    }
+}
+
+fn inc32(counter: u128) -> u128 {
+    let low = (counter as u32).wrapping_add(1);
+    (counter & !0xffff_ffff) | low as u128
+}
+
+/// Absorb `data`, zero-padded to whole blocks, into the GHASH state `y`.
+fn ghash_update(h: u128, mut y: u128, data: &[u8]) -> u128 {
+    for chunk in data.chunks(16) {
+        let mut block = [0u8; 16];
+        block[..chunk.len()].copy_from_slice(chunk);
+        y = gf_mul(y ^ u128::from_be_bytes(block), h);
+    }
+    y
+}
+
+/// Multiplication in GF(2^128) with the GCM bit order, without secret
+/// dependent branches.
+fn gf_mul(x: u128, y: u128) -> u128 {
+    let mut z = 0u128;
+    let mut v = y;
+    for i in 0..128 {
+        let bit = (x >> (127 - i)) & 1;
+        z ^= v & bit.wrapping_neg();
+        v = (v >> 1) ^ (GCM_R & (v & 1).wrapping_neg());
+    }
+    z
+}
+
+#[cfg(test)]
+mod test {
+    use hex_literal::hex;
+
+    use crate::gcm::Sm4Gcm;
+    use crate::Sm4Error;
+
+    // RFC 8998 appendix A.1
+    #[test]
+    fn test_rfc8998() {
+        let key = hex!("0123456789ABCDEFFEDCBA9876543210");
+        let nonce = hex!("00001234567800000000ABCD");
+        let aad = hex!("FEEDFACEDEADBEEFFEEDFACEDEADBEEFABADDAD2");
+        let plaintext = hex!(
+            "AAAAAAAAAAAAAAAABBBBBBBBBBBBBBBBCCCCCCCCCCCCCCCCDDDDDDDDDDDDDDDD"
+            "EEEEEEEEEEEEEEEEFFFFFFFFFFFFFFFFEEEEEEEEEEEEEEEEAAAAAAAAAAAAAAAA"
+        );
+        let ciphertext = hex!(
+            "17F399F08C67D5EE19D0DC9969C4BB7D5FD46FD3756489069157B282BB200735"
+            "D82710CA5C22F0CCFA7CBF93D496AC15A56834CBCF98C397B4024A2691233B8D"
+        );
+        let tag = hex!("83DE3541E4C2B58177E065A9BF7B62EC");
+
+        let gcm = Sm4Gcm::new(&key).unwrap();
+        let sealed = gcm.encrypt(&nonce, &aad, &plaintext).unwrap();
+        assert_eq!(&ciphertext, &sealed[..64]);
+        assert_eq!(&tag, &sealed[64..]);
+        assert_eq!(
+            plaintext.to_vec(),
+            gcm.decrypt(&nonce, &aad, &sealed).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_gcm_rejects_forgery() {
+        let gcm = Sm4Gcm::new(&[0x24; 16]).unwrap();
+        for nonce in [&[0x01u8; 12][..], &[0x02; 1], &[0x03; 16], &[0x04; 60]] {
+            for len in [0, 1, 15, 16, 17, 100] {
+                let msg = vec![0x5a; len];
+                let sealed = gcm.encrypt(nonce, b"aad", &msg).unwrap();
+                assert_eq!(len + 16, sealed.len());
+                assert_eq!(msg, gcm.decrypt(nonce, b"aad", &sealed).unwrap());
+
+                for i in 0..sealed.len() {
+                    let mut forged = sealed.clone();
+                    forged[i] ^= 0x80;
+                    assert!(matches!(
+                        gcm.decrypt(nonce, b"aad", &forged),
+                        Err(Sm4Error::InvalidTag)
+                    ));
+                }
+                assert!(gcm.decrypt(nonce, b"aae", &sealed).is_err());
+            }
+        }
+        assert!(gcm.decrypt(&[0x01; 12], b"", &[0; 15]).is_err());
+        assert!(gcm.encrypt(&[], b"", b"msg").is_err());
+    }
+}
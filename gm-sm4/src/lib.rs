@@ -5,8 +5,12 @@ use std::error;
 use std::fmt::{Display, Formatter};
 use const_oid::ObjectIdentifier;
 
+pub mod gcm;
 
 pub const OID_SM4: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.156.10197.1.104");
+pub const OID_SM4_CBC: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.156.10197.1.104.2");
+pub const OID_SM4_CTR: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.156.10197.1.104.7");
+pub const OID_SM4_GCM: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.156.10197.1.104.8");
 
 static SBOX: [u8; 256] = [
     0xd6, 0x90, 0xe9, 0xfe, 0xcc, 0xe1, 0x3d, 0xb7, 0x16, 0xb6, 0x14, 0xc2, 0x28, 0xfb, 0x2c, 0x05,
@@ -42,6 +46,8 @@ pub enum Sm4Error {
     ErrorBlockSize,
     ErrorDataLen,
     InvalidLastU8,
+    ErrorNonceLen,
+    InvalidTag,
 }
 
 impl ::std::fmt::Debug for Sm4Error {
@@ -58,6 +64,8 @@ impl From<Sm4Error> for &str {
             Sm4Error::InvalidLastU8 => {
                 "the last u8 of cbc_decrypt out in SM4 must be positive which isn't greater than 16"
             }
+            Sm4Error::ErrorNonceLen => "the nonce of SM4-GCM must not be empty",
+            Sm4Error::InvalidTag => "the authentication tag of SM4-GCM does not match",
         }
     }
 }
@@ -75,6 +83,8 @@ impl Display for Sm4Error {
                 Sm4Error::InvalidLastU8 => {
                     "the last u8 of cbc_decrypt out in SM4 must be positive which isn't greater than 16"
                 }
+                Sm4Error::ErrorNonceLen => "the nonce of SM4-GCM must not be empty",
+                Sm4Error::InvalidTag => "the authentication tag of SM4-GCM does not match",
             }
         )
     }
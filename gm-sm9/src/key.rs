@@ -6,7 +6,7 @@ use crate::{
     SM9_HASH1_PREFIX, SM9_HASH2_PREFIX, SM9_HID_ENC, SM9_HID_EXCH, SM9_HID_SIGN, SM9_N_MINUS_ONE,
     SM9_POINT_MONT_P1, SM9_TWIST_POINT_MONT_P2,
 };
-use gm_sm3::sm3_hash;
+use gm_sm3::{hmac_sm3, sm3_hash, sm3_kdf, HmacSm3};
 use pkcs8::spki::AlgorithmIdentifier;
 use pkcs8::ObjectIdentifier;
 
@@ -61,7 +61,7 @@ impl Sm9EncKey {
         k_append.extend_from_slice(&c1_bytes[1..65]);
         k_append.extend_from_slice(&w_bytes);
         k_append.extend_from_slice(idb);
-        let k = kdf(&k_append, (255 + 32) as usize);
+        let k = sm3_kdf(&k_append, (255 + 32) as usize);
         fn is_zero(x: &Vec<u8>) -> bool {
             x.iter().all(|&byte| byte == 0)
         }
@@ -71,10 +71,9 @@ impl Sm9EncKey {
             let mlen = data.len() - (65 + 32);
             let k1 = &k[0..mlen];
             let k2 = &k[mlen..];
-            let u = sm3_hmac(k2, c2, 32);
-            if !u.as_slice().eq(c3) {
-                return Err(Sm9Error::InvalidDigest);
-            }
+            let mut mac = HmacSm3::new(&k2[..32]);
+            mac.update(c2);
+            mac.verify(c3).map_err(|_| Sm9Error::InvalidDigest)?;
             let m = xor(c2, &k1, k1.len());
             Ok(m)
         } else {
@@ -130,7 +129,7 @@ impl Sm9EncMasterKey {
             k_append.extend_from_slice(&cbuf[1..cbuf.len()]);
             k_append.extend_from_slice(gbuf);
             k_append.extend_from_slice(idb);
-            k = kdf(&k_append, (255 + 32) as usize);
+            k = sm3_kdf(&k_append, (255 + 32) as usize);
             fn is_zero(x: &Vec<u8>) -> bool {
                 x.iter().all(|&byte| byte == 0)
             }
@@ -143,7 +142,7 @@ impl Sm9EncMasterKey {
         let k1 = &k[0..data.len()];
         let k2 = &k[data.len()..];
         let c2 = xor(k1, &data, data.len());
-        let c3 = sm3_hmac(k2, &c2, 32usize);
+        let c3 = hmac_sm3(&k2[..32], &c2);
         let mut c: Vec<u8> = vec![];
         c.extend_from_slice(&c1.to_bytes_be());
         c.extend_from_slice(&c3);
@@ -188,40 +187,6 @@ impl Sm9EncMasterKey {
     }
 }
 
-const BLOCK_SIZE: usize = 64;
-
-fn sm3_hmac(key: &[u8], message: &[u8], klen: usize) -> Vec<u8> {
-    let mut ipad = [0x36u8; BLOCK_SIZE];
-    let mut opad = [0x5cu8; BLOCK_SIZE];
-
-    let mut key_block = [0u8; 64];
-
-    // 如果密钥长度大于 BLOCK_SIZE，先进行哈希
-    if klen > BLOCK_SIZE {
-        key_block[..32].copy_from_slice(&sm3_hash(key));
-    } else {
-        key_block[..klen].copy_from_slice(&key[0..klen]);
-    };
-
-    // 准备 ipad 和 opad
-    for i in 0..BLOCK_SIZE {
-        ipad[i] ^= key_block[i];
-        opad[i] ^= key_block[i];
-    }
-
-    // 内层哈希: H((K ^ ipad) || message)
-    let mut ipad_append = vec![];
-    ipad_append.extend_from_slice(&ipad);
-    ipad_append.extend_from_slice(message);
-    let inner_result = sm3_hash(&ipad_append);
-
-    // 外层哈希: H((K ^ opad) || inner_result)
-    let mut opad_append = vec![];
-    opad_append.extend_from_slice(&opad);
-    opad_append.extend_from_slice(&inner_result);
-    sm3_hash(&opad_append).to_vec()
-}
-
 fn sm9_u256_hash1(id: &[u8], hid: u8) -> U256 {
     let ct1: [u8; 4] = [0x00, 0x00, 0x00, 0x01];
     let ct2: [u8; 4] = [0x00, 0x00, 0x00, 0x02];
@@ -270,33 +235,6 @@ fn sm9_u256_hash2(data: &[u8], wbuf: &[u8]) -> U256 {
     r
 }
 
-fn kdf(z: &[u8], klen: usize) -> Vec<u8> {
-    let mut ct = 0x00000001u32;
-    let bound = ((klen as f64) / 32.0).ceil() as u32;
-    let mut h_a = Vec::new();
-    for _i in 1..bound {
-        let mut prepend = Vec::new();
-        prepend.extend_from_slice(z);
-        prepend.extend_from_slice(&ct.to_be_bytes());
-
-        let h_a_i = sm3_hash(&prepend[..]);
-        h_a.extend_from_slice(&h_a_i);
-        ct += 1;
-    }
-
-    let mut prepend = Vec::new();
-    prepend.extend_from_slice(z);
-    prepend.extend_from_slice(&ct.to_be_bytes());
-
-    let last = sm3_hash(&prepend[..]);
-    if klen % 32 == 0 {
-        h_a.extend_from_slice(&last);
-    } else {
-        h_a.extend_from_slice(&last[0..(klen % 32)]);
-    }
-    h_a
-}
-
 #[derive(Copy, Debug, Clone)]
 pub struct Sm9SignKey {
     pub ppubs: TwistPoint,
@@ -462,7 +400,7 @@ pub fn exch_step_1b(
         pre_append.extend_from_slice(&g2);
         pre_append.extend_from_slice(&g3);
 
-        sk = kdf(&pre_append, klen);
+        sk = sm3_kdf(&pre_append, klen);
 
         fn is_zero(x: &Vec<u8>, klen: usize) -> bool {
             let mut ret = true;
@@ -519,7 +457,7 @@ pub fn exch_step_2a(
         pre_append.extend_from_slice(&g2);
         pre_append.extend_from_slice(&g3);
 
-        sk = kdf(&pre_append, klen);
+        sk = sm3_kdf(&pre_append, klen);
         fn is_zero(x: &Vec<u8>, klen: usize) -> bool {
             let mut ret = true;
             for i in 0..klen {